@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{self, Value};
+
+use super::error::BotError;
+use super::outbox::Outbox;
+use super::schedule::ChannelSender;
+
+/// Someone clicking a button (or using another interactive element) on one of the bot's messages.
+#[derive(Clone, Debug)]
+pub struct Action {
+    /// The id the button was created with. For buttons in attachments, this is the button's name.
+    pub action_id: String,
+
+    /// The value the button was created with, if it has one.
+    pub value: Option<String>,
+
+    /// The id of the user that clicked the button.
+    pub user_id: String,
+
+    /// The id of the channel the message with the button is in.
+    pub channel_id: String,
+
+    /// The timestamp of the message with the button, which can be used to update it.
+    pub message_ts: Option<String>,
+
+    /// Where Slack accepts replies to the action for the next half hour, if it gave one.
+    pub response_url: Option<String>
+}
+
+/// A trait implemented by types that handle actions, like clicks on buttons. Actions come in from
+/// your web server's thread, so handlers have to be `Send`.
+///
+/// The sender sends messages to the channel the action came from.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{Action, ActionHandler, ChannelSender};
+/// struct Approve;
+///
+/// impl ActionHandler for Approve {
+///     fn handle(&mut self, sender: &mut ChannelSender, action: &Action) {
+///         sender.send_message(format!("<@{}> approved the deploy", action.user_id));
+///     }
+/// }
+/// ```
+pub trait ActionHandler: Send {
+    /// Handle the action.
+    fn handle(&mut self, sender: &mut ChannelSender, action: &Action);
+}
+
+impl<F> ActionHandler for F where F: FnMut(&mut ChannelSender, &Action) + Send {
+    fn handle(&mut self, sender: &mut ChannelSender, action: &Action) {
+        self(sender, action);
+    }
+}
+
+/// Action handlers by action id, shared by the bot and its action handles.
+pub type SharedActions = Arc<Mutex<HashMap<String, Box<ActionHandler>>>>;
+
+/// A handle for passing interactivity payloads from Slack to a bot's action handlers.
+///
+/// Slack sends payloads to a web server of your own rather than over the bot's connection, so
+/// whatever handles those requests should give each payload to `handle_payload`. Handles can be
+/// used from any thread, whether or not the bot is running.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use slackbot::{SlackBot, Action, ChannelSender};
+/// let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// my_bot.on_action("approve", Box::new(|sender: &mut ChannelSender, action: &Action| {
+///     sender.send_message("Approved!");
+/// }));
+///
+/// let actions = my_bot.action_handle();
+/// // In your web server, with the request's `payload` form field:
+/// # let payload = "";
+/// actions.handle_payload(payload).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ActionHandle {
+    actions: SharedActions,
+    token: String,
+    outbox: Outbox
+}
+
+impl ActionHandle {
+    pub fn new<S: Into<String>>(actions: SharedActions, token: S, outbox: Outbox) -> Self {
+        ActionHandle {
+            actions: actions,
+            token: token.into(),
+            outbox: outbox
+        }
+    }
+
+    /// Call the handlers for the actions in an interactivity payload, given as the JSON in the
+    /// request's `payload` form field. Both `block_actions` payloads from Block Kit buttons and
+    /// `interactive_message` payloads from attachment buttons are understood, and actions
+    /// without a handler are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let actions = my_bot.action_handle();
+    /// let payload = r#"{"type": "block_actions", "user": {"id": "U12345"}, "channel": {"id": "C12345"}, "actions": []}"#;
+    /// assert!(actions.handle_payload(payload).is_ok());
+    /// assert!(actions.handle_payload("not json").is_err());
+    /// ```
+    pub fn handle_payload(&self, payload: &str) -> Result<(), BotError> {
+        let payload: Value = try!(serde_json::from_str(payload));
+        for action in try!(parse_actions(&payload)) {
+            let mut actions = self.actions.lock().unwrap();
+            match actions.get_mut(&action.action_id) {
+                Some(handler) => {
+                    info!("Got action: {}", action.action_id);
+                    let mut sender = ChannelSender::new(&self.token[..], &action.channel_id[..], self.outbox.clone());
+                    handler.handle(&mut sender, &action);
+                },
+                None => debug!("Ignoring action {} with no handler", action.action_id)
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The actions in an interactivity payload, which can be about more than one.
+fn parse_actions(payload: &Value) -> Result<Vec<Action>, BotError> {
+    let (id_field, message_ts) = match payload["type"].as_str() {
+        Some("block_actions") => ("action_id", payload["message"]["ts"].as_str()),
+        Some("interactive_message") => ("name", payload["message_ts"].as_str()),
+        Some(ty) => return Err(BotError::Slack(format!("Unsupported interactivity payload `{}`", ty))),
+        None => return Err(BotError::Slack("Interactivity payload didn't say what it's for".to_owned()))
+    };
+    let (user_id, channel_id) = match (payload["user"]["id"].as_str(), payload["channel"]["id"].as_str()) {
+        (Some(user_id), Some(channel_id)) => (user_id, channel_id),
+        _ => return Err(BotError::Slack("Interactivity payload didn't say who or where it's from".to_owned()))
+    };
+
+    let actions = payload["actions"].as_array().map_or(&[][..], |actions| &actions[..]);
+    Ok(actions.iter().filter_map(|action| {
+        action[id_field].as_str().map(|action_id| Action {
+            action_id: action_id.to_owned(),
+            value: action["value"].as_str().map(str::to_owned),
+            user_id: user_id.to_owned(),
+            channel_id: channel_id.to_owned(),
+            message_ts: message_ts.map(str::to_owned),
+            response_url: payload["response_url"].as_str().map(str::to_owned)
+        })
+    }).collect())
+}
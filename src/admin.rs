@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::CommandHandler;
+use super::config::Config;
+use super::messages::fill;
+use super::sender::CommandContext;
+
+/// The default name of the admin command. See `SlackBot::enable_admin_command`.
+pub const DEFAULT_ADMIN_COMMAND: &'static str = "_commands";
+
+/// What the admin command says about a command, besides its name.
+#[derive(Default)]
+struct CommandInfo {
+    description: Option<String>,
+    channels: Option<Vec<String>>,
+    authorized_only: bool,
+    cooldown: Option<u64>,
+    min_args: Option<usize>,
+    aliases: Vec<String>
+}
+
+/// The built-in admin command, which lists every command along with how it's restricted, for
+/// the people running the bot.
+pub struct AdminCommandHandler {
+    /// Looked at each time, so commands added while the bot is running show up.
+    command_names: Arc<Mutex<Vec<String>>>,
+    info: HashMap<String, CommandInfo>,
+    header: String
+}
+
+impl AdminCommandHandler {
+    pub fn new(command_names: Arc<Mutex<Vec<String>>>, config: &Config) -> Self {
+        let mut info: HashMap<String, CommandInfo> = HashMap::new();
+        for (name, description) in config.descriptions.iter() {
+            info.entry(name.clone()).or_insert_with(CommandInfo::default).description = Some(description.clone());
+        }
+        for (name, channels) in config.allowed_channels.iter() {
+            info.entry(name.clone()).or_insert_with(CommandInfo::default).channels = Some(channels.clone());
+        }
+        for name in config.authorizers.keys() {
+            info.entry(name.clone()).or_insert_with(CommandInfo::default).authorized_only = true;
+        }
+        for (name, &seconds) in config.cooldowns.iter() {
+            info.entry(name.clone()).or_insert_with(CommandInfo::default).cooldown = Some(seconds);
+        }
+        for (name, &(min, _)) in config.min_args.iter() {
+            info.entry(name.clone()).or_insert_with(CommandInfo::default).min_args = Some(min);
+        }
+        for (alias, target) in config.aliases.iter() {
+            info.entry(target.clone()).or_insert_with(CommandInfo::default).aliases.push(alias.clone());
+        }
+        for command in info.values_mut() {
+            command.aliases.sort();
+        }
+
+        AdminCommandHandler {
+            command_names: command_names,
+            info: info,
+            header: config.messages.admin_header.clone()
+        }
+    }
+
+    /// A line about a command, like `` `deploy` — Deploys the app (in #ops, authorized users
+    /// only) ``.
+    fn describe(&self, name: &str) -> String {
+        let info = match self.info.get(name) {
+            Some(info) => info,
+            None => return format!("`{}`", name)
+        };
+
+        let mut restrictions = Vec::new();
+        if let Some(ref channels) = info.channels {
+            restrictions.push(format!("in {}", channels.join(", ")));
+        }
+        if info.authorized_only {
+            restrictions.push("authorized users only".to_owned());
+        }
+        if let Some(seconds) = info.cooldown {
+            restrictions.push(format!("{}s cooldown", seconds));
+        }
+        if let Some(min) = info.min_args {
+            restrictions.push(format!("at least {} args", min));
+        }
+        if !info.aliases.is_empty() {
+            let aliases = info.aliases.iter().map(|alias| format!("`{}`", alias)).collect::<Vec<_>>();
+            restrictions.push(format!("aliases {}", aliases.join(", ")));
+        }
+
+        let mut line = format!("`{}`", name);
+        if let Some(ref description) = info.description {
+            line = format!("{} — {}", line, description);
+        }
+        if !restrictions.is_empty() {
+            line = format!("{} ({})", line, restrictions.join(", "));
+        }
+        line
+    }
+}
+
+impl CommandHandler for AdminCommandHandler {
+    fn handle(&mut self, ctx: &mut CommandContext, _: &Vec<String>) {
+        let lines = self.command_names.lock().unwrap().iter()
+            .map(|name| self.describe(&name[..]))
+            .collect::<Vec<_>>();
+        let count = lines.len().to_string();
+        let header = fill(&self.header[..], &[("count", &count[..])]);
+        let _ = ctx.respond_in_channel(format!("{}\n{}", header, lines.join("\n")));
+    }
+}
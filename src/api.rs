@@ -0,0 +1,339 @@
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+use hyper::Client;
+use hyper::client::Response;
+use hyper::header::Headers;
+use hyper::status::StatusCode;
+use serde_json::{self, Value};
+
+use super::error::BotError;
+
+const MAX_ATTEMPTS: u32 = 3;
+const INITIAL_RETRY_MILLIS: u64 = 500;
+
+/// Try something that talks to Slack a few times, waiting a little longer before each attempt,
+/// for as long as it fails with a transient error.
+pub fn with_retries<T, F>(mut attempt: F) -> Result<T, BotError> where F: FnMut() -> Result<T, BotError> {
+    let mut attempts = 1;
+    loop {
+        match attempt() {
+            Err(err) => match retry_delay(&err, attempts) {
+                Some(delay) => {
+                    debug!("Retrying after a transient error: {}", err);
+                    thread::sleep(delay);
+                    attempts += 1;
+                },
+                None => return Err(err)
+            },
+            result => return result
+        }
+    }
+}
+
+/// How long to wait before trying something again after it failed on its `attempts`th try, if
+/// it's worth trying again. Each wait is twice as long as the one before, unless Slack said how
+/// long to wait.
+pub fn retry_delay(err: &BotError, attempts: u32) -> Option<Duration> {
+    if !err.is_transient() || attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    match *err {
+        BotError::RateLimited { retry_after: Some(retry_after), .. } => Some(retry_after),
+        _ => Some(Duration::from_millis(INITIAL_RETRY_MILLIS << (attempts - 1)))
+    }
+}
+
+/// Call a Slack Web API method that the RtmClient doesn't wrap, returning the parsed response.
+///
+/// Responses that don't have `"ok": true` are turned into a `BotError::Api` with Slack's error
+/// code. Calls that fail with a transient error are retried a couple of times first.
+pub fn call(token: &str, method: &str, params: &[(&str, &str)]) -> Result<Value, BotError> {
+    with_retries(|| call_once(token, method, params))
+}
+
+/// A call to a Web API method that owns its params, so it can be made later on another thread,
+/// like the outbox's.
+pub struct Call {
+    token: String,
+    method: String,
+    params: Vec<(String, String)>
+}
+
+impl Call {
+    pub fn new(token: &str, method: &str, params: &[(&str, &str)]) -> Self {
+        Call {
+            token: token.to_owned(),
+            method: method.to_owned(),
+            params: params.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect()
+        }
+    }
+
+    /// Make the call once. Calls made from the outbox are tried again by the outbox itself, so
+    /// it isn't held up waiting to retry them.
+    pub fn send(&self) -> Result<Value, BotError> {
+        let params = self.params.iter().map(|&(ref name, ref value)| (&name[..], &value[..])).collect::<Vec<_>>();
+        call_once(&self.token[..], &self.method[..], &params[..])
+    }
+}
+
+// Params are posted as a form rather than put in the URL, where the token would end up in logs
+// and long messages would make the URL too long
+fn call_once(token: &str, method: &str, params: &[(&str, &str)]) -> Result<Value, BotError> {
+    let mut pairs = vec![("token", token)];
+    pairs.extend_from_slice(params);
+    let body = form_body(&pairs[..]);
+
+    let mut headers = Headers::new();
+    headers.set_raw("Content-Type", vec![b"application/x-www-form-urlencoded".to_vec()]);
+    let url = format!("https://slack.com/api/{}", method);
+    let response = match Client::new().post(&url[..]).headers(headers).body(&body[..]).send() {
+        Ok(response) => response,
+        Err(err) => return Err(BotError::Network(format!("{}", err)))
+    };
+    parse_response(method, response)
+}
+
+/// Upload a file to a Slack Web API method as a multipart form, along with the other params, and
+/// return the parsed response. Unlike `call`, this is only tried once, since uploads go through
+/// the outbox, which tries them again itself.
+pub fn upload(token: &str, method: &str, params: &[(&str, &str)], filename: &str, content: &str) -> Result<Value, BotError> {
+    let boundary = multipart_boundary(content);
+    let mut body = String::new();
+    for &(name, value) in Some(("token", token)).iter().chain(params.iter()) {
+        body.push_str(&format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value)[..]);
+    }
+    body.push_str(&format!("--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n", boundary, filename)[..]);
+    body.push_str(&format!("Content-Type: application/octet-stream\r\n\r\n{}\r\n--{}--\r\n", content, boundary)[..]);
+
+    let mut headers = Headers::new();
+    headers.set_raw("Content-Type", vec![format!("multipart/form-data; boundary={}", boundary).into_bytes()]);
+    let url = format!("https://slack.com/api/{}", method);
+    let response = match Client::new().post(&url[..]).headers(headers).body(&body[..]).send() {
+        Ok(response) => response,
+        Err(err) => return Err(BotError::Network(format!("{}", err)))
+    };
+    parse_response(method, response)
+}
+
+/// Download one of Slack's private files, which need the token to be sent along. Like `call`,
+/// transient errors are retried.
+pub fn download(token: &str, url: &str) -> Result<Vec<u8>, BotError> {
+    with_retries(|| download_once(token, url))
+}
+
+fn download_once(token: &str, url: &str) -> Result<Vec<u8>, BotError> {
+    let mut headers = Headers::new();
+    headers.set_raw("Authorization", vec![format!("Bearer {}", token).into_bytes()]);
+    let mut response = match Client::new().get(url).headers(headers).send() {
+        Ok(response) => response,
+        Err(err) => return Err(BotError::Network(format!("{}", err)))
+    };
+    if !response.status.is_success() {
+        return Err(BotError::Slack(format!("Downloading {} failed: {}", url, response.status)));
+    }
+
+    let mut content = Vec::new();
+    match response.read_to_end(&mut content) {
+        Ok(_) => Ok(content),
+        Err(err) => Err(BotError::Network(format!("{}", err)))
+    }
+}
+
+/// Encode params as an `application/x-www-form-urlencoded` body.
+fn form_body(params: &[(&str, &str)]) -> String {
+    params.iter()
+        .map(|&(name, value)| format!("{}={}", form_encode(name), form_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encode everything but letters, digits and `-._*`, with spaces as `+`, like browsers
+/// do for forms.
+fn form_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'*' => encoded.push(byte as char),
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)[..])
+        }
+    }
+    encoded
+}
+
+/// A multipart boundary that doesn't show up in the content being uploaded.
+fn multipart_boundary(content: &str) -> String {
+    let mut n = 0;
+    loop {
+        let boundary = format!("slackbot-boundary-{}", n);
+        if !content.contains(&boundary[..]) {
+            return boundary;
+        }
+        n += 1;
+    }
+}
+
+fn parse_response(method: &str, mut response: Response) -> Result<Value, BotError> {
+    let mut body = String::new();
+    if let Err(err) = response.read_to_string(&mut body) {
+        return Err(BotError::Network(format!("{}", err)));
+    }
+
+    let retry_after = response.headers.get_raw("Retry-After").and_then(parse_retry_after);
+    parse_body(method, response.status, retry_after, &body[..])
+}
+
+/// Turn a Web API response into its data, or the error it's for.
+fn parse_body(method: &str, status: StatusCode, retry_after: Option<Duration>, body: &str) -> Result<Value, BotError> {
+    if status == StatusCode::TooManyRequests {
+        return Err(BotError::RateLimited {
+            method: method.to_owned(),
+            retry_after: retry_after
+        });
+    }
+
+    // Errors from Slack's load balancers, or proxies on the way, aren't JSON
+    let data: Value = match serde_json::from_str(body) {
+        Ok(data) => data,
+        Err(_) if status.is_server_error() => return Err(BotError::Network(format!("{} failed: {}", method, status))),
+        Err(_) if !status.is_success() => return Err(BotError::Api {
+            method: method.to_owned(),
+            error: format!("http_{}", status.to_u16())
+        }),
+        Err(err) => return Err(BotError::Json(err))
+    };
+    match data.get("ok") {
+        Some(&Value::Bool(true)) => Ok(data),
+        _ => Err(BotError::Api {
+            method: method.to_owned(),
+            error: data.get("error").and_then(Value::as_str).unwrap_or("unknown_error").to_owned()
+        })
+    }
+}
+
+/// How long a `Retry-After` header says to wait. Slack always gives it in seconds.
+fn parse_retry_after(values: &[Vec<u8>]) -> Option<Duration> {
+    values.first()
+        .and_then(|value| String::from_utf8(value.clone()).ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// Open a direct message channel with a user, returning its id. If one is already open, Slack
+/// gives back the existing channel. Like `upload`, this is only tried once.
+pub fn open_im(token: &str, user_id: &str) -> Result<String, BotError> {
+    let data = try!(call_once(token, "im.open", &[("user", user_id)]));
+    im_channel_id(&data)
+}
+
+/// The id of the channel in an `im.open` response.
+fn im_channel_id(data: &Value) -> Result<String, BotError> {
+    match data.pointer("/channel/id") {
+        Some(&Value::String(ref id)) => Ok(id.to_owned()),
+        _ => Err(BotError::Slack("im.open didn't return a channel id".to_owned()))
+    }
+}
+
+/// React to a message with an emoji, treating a reaction that's already there as a success. Like
+/// `upload`, this is only tried once.
+pub fn add_reaction(token: &str, emoji: &str, channel_id: &str, ts: &str) -> Result<(), BotError> {
+    let params = [("name", emoji.trim_matches(':')), ("channel", channel_id), ("timestamp", ts)];
+    match call_once(token, "reactions.add", &params) {
+        Ok(_) => Ok(()),
+        Err(BotError::Api { ref error, .. }) if error == "already_reacted" => Ok(()),
+        Err(err) => Err(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyper::status::StatusCode;
+    use serde_json;
+
+    use super::{form_body, im_channel_id, parse_body, parse_retry_after, retry_delay};
+    use super::super::error::BotError;
+
+    #[test]
+    fn encodes_params_as_a_form() {
+        let body = form_body(&[("token", "xoxb-123"), ("text", "Hi & bye, 100% <@U123>")]);
+        assert_eq!(body, "token=xoxb-123&text=Hi+%26+bye%2C+100%25+%3C%40U123%3E");
+    }
+
+    #[test]
+    fn encodes_unicode_in_a_form() {
+        assert_eq!(form_body(&[("text", "café ✓")]), "text=caf%C3%A9+%E2%9C%93");
+    }
+
+    #[test]
+    fn finds_the_channel_in_an_im_open_response() {
+        let data = serde_json::from_str(r#"{
+            "ok": true,
+            "no_op": true,
+            "already_open": true,
+            "channel": {"id": "D947RLWRX"}
+        }"#).unwrap();
+        assert_eq!(im_channel_id(&data).unwrap(), "D947RLWRX");
+    }
+
+    #[test]
+    fn fails_without_a_channel_in_an_im_open_response() {
+        let data = serde_json::from_str(r#"{"ok": true}"#).unwrap();
+        assert!(im_channel_id(&data).is_err());
+    }
+
+    #[test]
+    fn reads_retry_after_in_seconds() {
+        assert_eq!(parse_retry_after(&[b" 30 ".to_vec()]), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after(&[b"Wed, 21 Oct 2015 07:28:00 GMT".to_vec()]), None);
+        assert_eq!(parse_retry_after(&[]), None);
+    }
+
+    #[test]
+    fn waits_as_long_as_slack_says_when_rate_limited() {
+        let err = parse_body("chat.postMessage", StatusCode::TooManyRequests, Some(Duration::from_secs(30)), "").unwrap_err();
+        match err {
+            BotError::RateLimited { ref method, retry_after } => {
+                assert_eq!(method, "chat.postMessage");
+                assert_eq!(retry_after, Some(Duration::from_secs(30)));
+            },
+            ref err => panic!("Expected to be rate limited, got {:?}", err)
+        }
+        assert_eq!(retry_delay(&err, 1), Some(Duration::from_secs(30)));
+        assert_eq!(retry_delay(&err, 3), None);
+    }
+
+    #[test]
+    fn backs_off_when_rate_limited_without_retry_after() {
+        let err = parse_body("chat.postMessage", StatusCode::TooManyRequests, None, "").unwrap_err();
+        assert_eq!(retry_delay(&err, 1), Some(Duration::from_millis(500)));
+        assert_eq!(retry_delay(&err, 2), Some(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn error_pages_that_arent_json_are_api_errors() {
+        let err = parse_body("chat.postMessage", StatusCode::ServiceUnavailable, None, "<html>Down</html>").unwrap_err();
+        assert!(err.is_transient());
+        match err {
+            BotError::Network(_) => {},
+            err => panic!("Expected a network error, got {:?}", err)
+        }
+
+        match parse_body("chat.postMessage", StatusCode::NotFound, None, "Not found").unwrap_err() {
+            BotError::Api { ref error, .. } => assert_eq!(error, "http_404"),
+            err => panic!("Expected an API error, got {:?}", err)
+        }
+    }
+
+    #[test]
+    fn reads_errors_from_slack() {
+        match parse_body("chat.postMessage", StatusCode::Ok, None, r#"{"ok": false, "error": "channel_not_found"}"#).unwrap_err() {
+            BotError::Api { ref error, .. } => assert_eq!(error, "channel_not_found"),
+            err => panic!("Expected an API error, got {:?}", err)
+        }
+        assert!(parse_body("chat.postMessage", StatusCode::Ok, None, r#"{"ok": true}"#).is_ok());
+    }
+}
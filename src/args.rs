@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+/// A command's arguments, with helpers for pulling typed values and flags out of them.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{SlackBot, CommandContext, Args};
+/// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// my_bot.on("roll", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///     let args = Args::new(args);
+///     let sides = args.get_int(0).unwrap_or(6);
+///     let count = args.named("--count").and_then(|count| count.parse().ok()).unwrap_or(1);
+///     ctx.respond_in_channel(format!("Rolling {} {}-sided dice", count, sides));
+/// }));
+/// ```
+pub struct Args<'a> {
+    args: &'a [String]
+}
+
+impl<'a> Args<'a> {
+    pub fn new(args: &'a [String]) -> Self {
+        Args {
+            args: args
+        }
+    }
+
+    /// The argument at a position, if there is one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::Args;
+    /// let args = vec!["deploy".to_owned(), "prod".to_owned()];
+    /// assert_eq!(Args::new(&args).get(1), Some("prod"));
+    /// ```
+    pub fn get(&self, index: usize) -> Option<&'a str> {
+        self.args.get(index).map(|arg| &arg[..])
+    }
+
+    /// The argument at a position as a number, if there is one and it's a number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::Args;
+    /// let args = vec!["42".to_owned(), "many".to_owned()];
+    /// let args = Args::new(&args);
+    /// assert_eq!(args.get_int(0), Some(42));
+    /// assert_eq!(args.get_int(1), None);
+    /// ```
+    pub fn get_int(&self, index: usize) -> Option<i64> {
+        self.get(index).and_then(|arg| arg.parse().ok())
+    }
+
+    /// Whether a flag like `--verbose` was given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::Args;
+    /// let args = vec!["status".to_owned(), "--verbose".to_owned()];
+    /// assert!(Args::new(&args).flag("--verbose"));
+    /// ```
+    pub fn flag(&self, name: &str) -> bool {
+        self.args.iter().any(|arg| *arg == name)
+    }
+
+    /// The value of an option like `--count`, given as either `--count 5` or `--count=5`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::Args;
+    /// let args = vec!["--count".to_owned(), "5".to_owned(), "--env=prod".to_owned()];
+    /// let args = Args::new(&args);
+    /// assert_eq!(args.named("--count"), Some("5"));
+    /// assert_eq!(args.named("--env"), Some("prod"));
+    /// ```
+    pub fn named(&self, name: &str) -> Option<&'a str> {
+        for (i, arg) in self.args.iter().enumerate() {
+            if *arg == name {
+                return self.get(i + 1);
+            }
+            if arg.starts_with(name) && arg[name.len()..].starts_with('=') {
+                return Some(&arg[name.len() + 1..]);
+            }
+        }
+        None
+    }
+
+    /// How many arguments there are.
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Whether there aren't any arguments.
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+}
+
+/// A command's arguments, split into options like `--env=prod` and everything else.
+///
+/// An option like `--force` is a flag, and the argument after it is positional, unless the
+/// option has been declared as taking a value (see `SlackBot::options_taking_values`), in which
+/// case `--env prod` gives `env` the value `prod`. Any option can be given a value as
+/// `--env=prod`. Anything after `--` is positional, even if it looks like an option.
+///
+/// The bot splits every command's arguments like this before handling it, in
+/// `CommandContext::parsed_args`.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{SlackBot, CommandContext};
+/// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// my_bot.options_taking_values("deploy", &["env"]);
+/// my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///     let env = ctx.parsed_args.values("env").pop().unwrap_or("staging").to_owned();
+///     let services = ctx.parsed_args.positional().join(", ");
+///     ctx.respond_in_channel(format!("Deploying {} to {}", services, env));
+/// }));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ParsedArgs {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>
+}
+
+impl ParsedArgs {
+    /// Split up some arguments, where only options given as `--name=value` have values.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::ParsedArgs;
+    /// let args = vec!["--force", "api", "--env=prod", "--", "--literal"];
+    /// let parsed = ParsedArgs::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+    /// assert_eq!(parsed.positional(), &["api".to_owned(), "--literal".to_owned()]);
+    /// assert_eq!(parsed.options()["env"], Some("prod".to_owned()));
+    /// assert_eq!(parsed.options()["force"], None);
+    /// ```
+    pub fn parse(args: &[String]) -> Self {
+        ParsedArgs::parse_with_values(args, &[])
+    }
+
+    /// Split up some arguments, where the options in `value_options` (without their leading
+    /// `--`) take the argument after them as their value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::ParsedArgs;
+    /// let args = vec!["--env", "prod", "--force", "api"];
+    /// let parsed = ParsedArgs::parse_with_values(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(), &["env"]);
+    /// assert_eq!(parsed.positional(), &["api".to_owned()]);
+    /// assert_eq!(parsed.options()["env"], Some("prod".to_owned()));
+    /// assert_eq!(parsed.options()["force"], None);
+    /// ```
+    pub fn parse_with_values(args: &[String], value_options: &[&str]) -> Self {
+        let mut parsed = ParsedArgs::default();
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.positional.extend(args.cloned());
+                break;
+            }
+            if !arg.starts_with("--") {
+                parsed.positional.push(arg.clone());
+                continue;
+            }
+
+            let option = &arg[2..];
+            if let Some(i) = option.find('=') {
+                parsed.options.push((option[..i].to_owned(), Some(option[i + 1..].to_owned())));
+            } else if value_options.contains(&option) {
+                let takes_value = args.peek().map_or(false, |next| !next.starts_with("--"));
+                let value = if takes_value { args.next().cloned() } else { None };
+                parsed.options.push((option.to_owned(), value));
+            } else {
+                parsed.options.push((option.to_owned(), None));
+            }
+        }
+        parsed
+    }
+
+    /// The arguments that aren't options or their values, in order.
+    pub fn positional(&self) -> &[String] {
+        &self.positional[..]
+    }
+
+    /// The options by name, without their leading `--`, and their values if they have one. When
+    /// an option is given more than once, the last one wins.
+    pub fn options(&self) -> HashMap<String, Option<String>> {
+        self.options.iter().cloned().collect()
+    }
+
+    /// Every value an option was given, for options that can be repeated like `--tag a --tag b`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::ParsedArgs;
+    /// let args = vec!["--tag".to_owned(), "a".to_owned(), "--tag=b".to_owned()];
+    /// assert_eq!(ParsedArgs::parse_with_values(&args, &["tag"]).values("tag"), vec!["a", "b"]);
+    /// ```
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.options.iter()
+            .filter(|&&(ref option, _)| option == name)
+            .filter_map(|&(_, ref value)| value.as_ref().map(|value| &value[..]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParsedArgs;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn flags_before_positional_arguments_dont_take_them() {
+        let parsed = ParsedArgs::parse(&args(&["--force", "api", "web"]));
+        assert_eq!(parsed.positional(), &args(&["api", "web"])[..]);
+        assert_eq!(parsed.options()["force"], None);
+    }
+
+    #[test]
+    fn declared_options_take_the_next_argument() {
+        let parsed = ParsedArgs::parse_with_values(&args(&["--env", "prod", "--force", "api"]), &["env"]);
+        assert_eq!(parsed.positional(), &args(&["api"])[..]);
+        assert_eq!(parsed.options()["env"], Some("prod".to_owned()));
+        assert_eq!(parsed.options()["force"], None);
+    }
+
+    #[test]
+    fn declared_options_dont_take_another_option() {
+        let parsed = ParsedArgs::parse_with_values(&args(&["--env", "--force"]), &["env"]);
+        assert_eq!(parsed.options()["env"], None);
+        assert_eq!(parsed.options()["force"], None);
+    }
+
+    #[test]
+    fn any_option_can_be_given_a_value_with_equals() {
+        let parsed = ParsedArgs::parse(&args(&["--env=prod", "--note=a=b", "api"]));
+        assert_eq!(parsed.positional(), &args(&["api"])[..]);
+        assert_eq!(parsed.options()["env"], Some("prod".to_owned()));
+        assert_eq!(parsed.options()["note"], Some("a=b".to_owned()));
+    }
+
+    #[test]
+    fn keeps_every_value_of_repeated_options() {
+        let parsed = ParsedArgs::parse_with_values(&args(&["--tag", "a", "--tag=b", "--tag", "c"]), &["tag"]);
+        assert_eq!(parsed.values("tag"), vec!["a", "b", "c"]);
+        assert_eq!(parsed.options()["tag"], Some("c".to_owned()));
+    }
+
+    #[test]
+    fn everything_after_a_double_dash_is_positional() {
+        let parsed = ParsedArgs::parse_with_values(&args(&["--force", "--", "--env", "prod", "--"]), &["env"]);
+        assert_eq!(parsed.positional(), &args(&["--env", "prod", "--"])[..]);
+        assert!(parsed.values("env").is_empty());
+        assert_eq!(parsed.options().len(), 1);
+    }
+}
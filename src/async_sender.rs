@@ -0,0 +1,144 @@
+use slack::User;
+
+use super::api;
+use super::error::BotError;
+use super::outbox::Outbox;
+
+/// The sender of a command to the bot, for handlers that run off the bot's event loop.
+///
+/// Unlike `Sender`, this doesn't borrow the connection to Slack, so it can be cloned and moved to
+/// other threads. Everything it sends goes through Slack's web API.
+#[derive(Clone)]
+pub struct AsyncSender {
+    token: String,
+    outbox: Outbox,
+
+    /// The user that sent the command.
+    pub user: User,
+
+    /// The id of the channel the command came from.
+    pub channel_id: String,
+
+    /// The timestamp of the message the command came from, which Slack uses to identify it.
+    pub ts: String,
+
+    /// The timestamp of the thread the command came from. If the command wasn't sent in a thread,
+    /// this is the timestamp of the command's message, so replying to it starts a new thread.
+    pub thread_ts: String
+}
+
+impl AsyncSender {
+    pub fn new<A, B, C, D>(token: A, channel_id: B, ts: C, thread_ts: D, user: User, outbox: Outbox) -> Self
+        where A: Into<String>, B: Into<String>, C: Into<String>, D: Into<String> {
+
+        AsyncSender {
+            token: token.into(),
+            outbox: outbox,
+            user: user,
+            channel_id: channel_id.into(),
+            ts: ts.into(),
+            thread_ts: thread_ts.into()
+        }
+    }
+
+    /// Send a message to the channel that the message came from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate futures;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::{SlackBot, AsyncSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_async("say-hello", Box::new(|sender: AsyncSender, args: Vec<String>| {
+    ///     futures::future::lazy(move || sender.respond_in_channel("Hello, world!"))
+    /// }));
+    /// # }
+    /// ```
+    pub fn respond_in_channel<S: Into<String>>(&self, message: S) -> Result<(), BotError> {
+        let message = message.into();
+        let params = [("channel", &self.channel_id[..]), ("text", &message[..]), ("as_user", "true")];
+        self.post(&self.channel_id[..], &params)
+    }
+
+    /// Send a message to the thread that the message came from, starting a new thread if it wasn't
+    /// already in one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate futures;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::{SlackBot, AsyncSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_async("say-hello", Box::new(|sender: AsyncSender, args: Vec<String>| {
+    ///     futures::future::lazy(move || sender.respond_in_thread("Hello, thread!"))
+    /// }));
+    /// # }
+    /// ```
+    pub fn respond_in_thread<S: Into<String>>(&self, message: S) -> Result<(), BotError> {
+        let message = message.into();
+        let params = [
+            ("channel", &self.channel_id[..]),
+            ("text", &message[..]),
+            ("thread_ts", &self.thread_ts[..]),
+            ("as_user", "true")
+        ];
+        self.post(&self.channel_id[..], &params)
+    }
+
+    /// Send a message directly to the user that sent the command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate futures;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::{SlackBot, AsyncSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_async("whisper", Box::new(|sender: AsyncSender, args: Vec<String>| {
+    ///     futures::future::lazy(move || sender.respond_in_dm("Just between you and me..."))
+    /// }));
+    /// # }
+    /// ```
+    pub fn respond_in_dm<S: Into<String>>(&self, message: S) -> Result<(), BotError> {
+        let (token, user_id) = (self.token.clone(), self.user.id.clone());
+        let im_id = try!(self.outbox.send(&self.user.id[..], move || api::open_im(&token[..], &user_id[..])).wait());
+        let message = message.into();
+        let params = [("channel", &im_id[..]), ("text", &message[..]), ("as_user", "true")];
+        self.post(&im_id[..], &params)
+    }
+
+    /// React to the command's message with an emoji, given by name with or without colons.
+    /// Reacting with an emoji that's already there isn't an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate futures;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::{SlackBot, AsyncSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_async("deploy", Box::new(|sender: AsyncSender, args: Vec<String>| {
+    ///     futures::future::lazy(move || {
+    ///         try!(sender.react("eyes"));
+    ///         // ...deploy...
+    ///         sender.react("white_check_mark")
+    ///     })
+    /// }));
+    /// # }
+    /// ```
+    pub fn react(&self, emoji: &str) -> Result<(), BotError> {
+        let (token, emoji, channel_id, ts) = (self.token.clone(), emoji.to_owned(), self.channel_id.clone(), self.ts.clone());
+        self.outbox.send(&self.channel_id[..], move || api::add_reaction(&token[..], &emoji[..], &channel_id[..], &ts[..])).wait()
+    }
+
+    fn post(&self, channel_id: &str, params: &[(&str, &str)]) -> Result<(), BotError> {
+        let call = api::Call::new(&self.token[..], "chat.postMessage", params);
+        self.outbox.send(channel_id, move || call.send()).wait().map(|_| ())
+    }
+}
@@ -0,0 +1,97 @@
+/// A message attachment, for responses that need more than plain text.
+///
+/// See the [Slack docs](https://api.slack.com/docs/message-attachments) for how attachments are
+/// displayed.
+///
+/// # Examples
+///
+/// ```
+/// use slackbot::Attachment;
+///
+/// let attachment = Attachment::new()
+///     .color("good")
+///     .title("Deploy finished")
+///     .text("Everything went smoothly.")
+///     .field("Environment", "production")
+///     .field("Duration", "42s");
+/// ```
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Attachment {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fallback: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title_link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_url: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<AttachmentField>
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct AttachmentField {
+    title: String,
+    value: String,
+    short: bool
+}
+
+impl Attachment {
+    /// Create an empty attachment.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the plain text summary shown by clients that can't display attachments.
+    pub fn fallback<S: Into<String>>(mut self, fallback: S) -> Self {
+        self.fallback = Some(fallback.into());
+        self
+    }
+
+    /// Set the color of the sidebar. This can be `good`, `warning`, `danger`, or a hex color code
+    /// like `#439FE0`.
+    pub fn color<S: Into<String>>(mut self, color: S) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    /// Set the bold title at the top of the attachment.
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Make the title link to a URL.
+    pub fn title_link<S: Into<String>>(mut self, url: S) -> Self {
+        self.title_link = Some(url.into());
+        self
+    }
+
+    /// Set the main text of the attachment.
+    pub fn text<S: Into<String>>(mut self, text: S) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    /// Show an image from a URL in the attachment.
+    pub fn image_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.image_url = Some(url.into());
+        self
+    }
+
+    /// Add a field to the table at the bottom of the attachment.
+    pub fn field<A, B>(mut self, title: A, value: B) -> Self
+        where A: Into<String>, B: Into<String> {
+
+        self.fields.push(AttachmentField {
+            title: title.into(),
+            value: value.into(),
+            short: false
+        });
+        self
+    }
+}
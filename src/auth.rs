@@ -0,0 +1,41 @@
+use slack::User;
+
+/// A trait implemented by types that decide who can run a command.
+///
+/// A list of user ids or user names can be used directly, or you can write your own to check with
+/// something else, like an external access control service.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate slack;
+/// # extern crate slackbot;
+/// # fn main() {
+/// use slack::User;
+/// use slackbot::Authorizer;
+///
+/// struct AdminsOnly;
+///
+/// impl Authorizer for AdminsOnly {
+///     fn is_authorized(&self, user: &User, command_name: &str) -> bool {
+///         user.is_admin.unwrap_or(false)
+///     }
+/// }
+/// # }
+/// ```
+pub trait Authorizer {
+    /// Whether the user can run the command.
+    fn is_authorized(&self, user: &User, command_name: &str) -> bool;
+}
+
+impl Authorizer for Vec<String> {
+    fn is_authorized(&self, user: &User, _: &str) -> bool {
+        self.iter().any(|allowed| *allowed == user.id || *allowed == user.name)
+    }
+}
+
+impl<F> Authorizer for F where F: Fn(&User, &str) -> bool {
+    fn is_authorized(&self, user: &User, command_name: &str) -> bool {
+        self(user, command_name)
+    }
+}
@@ -0,0 +1,211 @@
+/// A Block Kit block, the building piece of a modern Slack message layout.
+///
+/// See the [Slack docs](https://api.slack.com/block-kit) for how blocks are displayed.
+///
+/// # Examples
+///
+/// ```
+/// use slackbot::{Block, Button, Actions};
+///
+/// let blocks = vec![
+///     Block::section("*Deploy finished* in 42s"),
+///     Block::Divider,
+///     Block::context("Deployed by deploy-bot"),
+///     Block::Actions(Actions::new().button(Button::new("Roll back", "rollback").style("danger")))
+/// ];
+/// ```
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Section(Section),
+    Divider,
+    Context(Context),
+    Actions(Actions)
+}
+
+impl Block {
+    /// A section block with some markdown text, the most common kind of block.
+    pub fn section<S: Into<String>>(text: S) -> Self {
+        Block::Section(Section::new(text))
+    }
+
+    /// A context block with a single piece of markdown text, for small print.
+    pub fn context<S: Into<String>>(text: S) -> Self {
+        Block::Context(Context::new().text(text))
+    }
+}
+
+/// Some text in a block, either formatted with Slack's markdown or shown as plain text.
+#[derive(Serialize, Clone, Debug)]
+pub struct Text {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    text: String
+}
+
+impl Text {
+    /// Text formatted with Slack's markdown, like `*bold*` and `<https://example.com|links>`.
+    pub fn markdown<S: Into<String>>(text: S) -> Self {
+        Text {
+            ty: "mrkdwn",
+            text: text.into()
+        }
+    }
+
+    /// Text that's shown exactly as it's written.
+    pub fn plain<S: Into<String>>(text: S) -> Self {
+        Text {
+            ty: "plain_text",
+            text: text.into()
+        }
+    }
+}
+
+/// A block of text, optionally with a table of fields and a button beside it.
+///
+/// # Examples
+///
+/// ```
+/// use slackbot::{Button, Section};
+///
+/// let section = Section::new("*Deploy finished*")
+///     .field("*Environment*\nproduction")
+///     .field("*Duration*\n42s")
+///     .accessory(Button::new("View logs", "view_logs"));
+/// ```
+#[derive(Serialize, Clone, Debug)]
+pub struct Section {
+    text: Text,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fields: Vec<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accessory: Option<Button>
+}
+
+impl Section {
+    /// Create a section with some markdown text.
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Section {
+            text: Text::markdown(text),
+            fields: Vec::new(),
+            accessory: None
+        }
+    }
+
+    /// Add a field of markdown text, which are shown two to a row under the section's text.
+    pub fn field<S: Into<String>>(mut self, text: S) -> Self {
+        self.fields.push(Text::markdown(text));
+        self
+    }
+
+    /// Show a button beside the section's text.
+    pub fn accessory(mut self, button: Button) -> Self {
+        self.accessory = Some(button);
+        self
+    }
+}
+
+impl From<Section> for Block {
+    fn from(section: Section) -> Self {
+        Block::Section(section)
+    }
+}
+
+/// A block of small text, for things like who did something and when.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Context {
+    elements: Vec<Text>
+}
+
+impl Context {
+    /// Create an empty context block.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a piece of markdown text.
+    pub fn text<S: Into<String>>(mut self, text: S) -> Self {
+        self.elements.push(Text::markdown(text));
+        self
+    }
+}
+
+impl From<Context> for Block {
+    fn from(context: Context) -> Self {
+        Block::Context(context)
+    }
+}
+
+/// A block of buttons.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Actions {
+    elements: Vec<Button>
+}
+
+impl Actions {
+    /// Create an empty actions block.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a button.
+    pub fn button(mut self, button: Button) -> Self {
+        self.elements.push(button);
+        self
+    }
+}
+
+impl From<Actions> for Block {
+    fn from(actions: Actions) -> Self {
+        Block::Actions(actions)
+    }
+}
+
+/// A button, which tells Slack's interactivity endpoint its action id and value when it's clicked.
+#[derive(Serialize, Clone, Debug)]
+pub struct Button {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    text: Text,
+    action_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    style: Option<String>
+}
+
+impl Button {
+    /// Create a button with a label and the id of the action it triggers.
+    pub fn new<A, B>(label: A, action_id: B) -> Self
+        where A: Into<String>, B: Into<String> {
+
+        Button {
+            ty: "button",
+            text: Text::plain(label),
+            action_id: action_id.into(),
+            value: None,
+            url: None,
+            style: None
+        }
+    }
+
+    /// Set the value that's sent along with the action.
+    pub fn value<S: Into<String>>(mut self, value: S) -> Self {
+        self.value = Some(value.into());
+        self
+    }
+
+    /// Open a URL in the user's browser when the button is clicked.
+    pub fn url<S: Into<String>>(mut self, url: S) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Set the button's color, which can be `primary` or `danger`.
+    pub fn style<S: Into<String>>(mut self, style: S) -> Self {
+        self.style = Some(style.into());
+        self
+    }
+}
@@ -0,0 +1,177 @@
+use super::SlackBot;
+use super::error::BotError;
+
+/// Sets up a bot's options in one go, for when there are a lot of them.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::SlackBotBuilder;
+/// let my_bot = SlackBotBuilder::new("bot", "YOUR_API_TOKEN")
+///     .prefix("!deploy")
+///     .respond_to_mentions(true)
+///     .auto_reconnect(Some(10))
+///     .rate_limit(10)
+///     .build()
+///     .unwrap();
+/// ```
+pub struct SlackBotBuilder {
+    name: String,
+    token: String,
+    prefix: Option<String>,
+    respond_to_mentions: bool,
+    default_command: Option<Option<String>>,
+    explain_channel_restrictions: bool,
+    handled_subtypes: Vec<String>,
+    rate_limit: Option<u32>,
+    max_args: Option<usize>,
+    max_message_len: Option<usize>,
+    explain_input_limits: bool,
+    default_help: bool,
+    auto_reconnect: Option<Option<usize>>
+}
+
+impl SlackBotBuilder {
+    pub fn new<A, B>(name: A, token: B) -> Self
+        where A: Into<String>, B: Into<String> {
+
+        SlackBotBuilder {
+            name: name.into(),
+            token: token.into(),
+            prefix: None,
+            respond_to_mentions: false,
+            default_command: None,
+            explain_channel_restrictions: false,
+            handled_subtypes: Vec::new(),
+            rate_limit: None,
+            max_args: None,
+            max_message_len: None,
+            explain_input_limits: false,
+            default_help: true,
+            auto_reconnect: None
+        }
+    }
+
+    /// The prefix commands start with. See `SlackBot::with_prefix`.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_owned());
+        self
+    }
+
+    /// Whether commands can start with a mention of the bot. See `SlackBot::respond_to_mentions`.
+    pub fn respond_to_mentions(mut self, enabled: bool) -> Self {
+        self.respond_to_mentions = enabled;
+        self
+    }
+
+    /// Whether to explain when a command can't be used in a channel. See
+    /// `SlackBot::explain_channel_restrictions`.
+    pub fn explain_channel_restrictions(mut self, enabled: bool) -> Self {
+        self.explain_channel_restrictions = enabled;
+        self
+    }
+
+    /// Message subtypes to handle commands from. See `SlackBot::handle_subtypes`.
+    pub fn handle_subtypes(mut self, subtypes: Vec<String>) -> Self {
+        self.handled_subtypes = subtypes;
+        self
+    }
+
+    /// How many commands each user can send a minute. See `SlackBot::rate_limit`.
+    pub fn rate_limit(mut self, max_per_minute: u32) -> Self {
+        self.rate_limit = Some(max_per_minute);
+        self
+    }
+
+    /// The most arguments a command can have. See `SlackBot::max_args`.
+    pub fn max_args(mut self, n: usize) -> Self {
+        self.max_args = Some(n);
+        self
+    }
+
+    /// The longest message that can have a command. See `SlackBot::max_message_len`.
+    pub fn max_message_len(mut self, bytes: usize) -> Self {
+        self.max_message_len = Some(bytes);
+        self
+    }
+
+    /// Whether to explain when a command is over the input limits. See
+    /// `SlackBot::explain_input_limits`.
+    pub fn explain_input_limits(mut self, enabled: bool) -> Self {
+        self.explain_input_limits = enabled;
+        self
+    }
+
+    /// The command to run for the prefix on its own. See `SlackBot::default_command`.
+    pub fn default_command(mut self, command_name: &str) -> Self {
+        self.default_command = Some(Some(command_name.to_owned()));
+        self
+    }
+
+    /// Ignore the prefix on its own. See `SlackBot::disable_default_command`.
+    pub fn disable_default_command(mut self) -> Self {
+        self.default_command = Some(None);
+        self
+    }
+
+    /// Don't answer `help` with a list of commands. See `SlackBot::disable_default_help`.
+    pub fn disable_default_help(mut self) -> Self {
+        self.default_help = false;
+        self
+    }
+
+    /// Reconnect when the connection to Slack drops. See `SlackBot::with_auto_reconnect`.
+    pub fn auto_reconnect(mut self, max_retries: Option<usize>) -> Self {
+        self.auto_reconnect = Some(max_retries);
+        self
+    }
+
+    /// Create the bot, or an error if any of the options are invalid.
+    pub fn build(self) -> Result<SlackBot, BotError> {
+        self.build_with_state(())
+    }
+
+    /// Create a bot that keeps track of some state, or an error if any of the options are
+    /// invalid. See `SlackBot::new_with_state`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBotBuilder;
+    /// let counter_bot = SlackBotBuilder::new("bot", "YOUR_API_TOKEN")
+    ///     .disable_default_help()
+    ///     .build_with_state(0)
+    ///     .unwrap();
+    /// ```
+    pub fn build_with_state<T>(self, state: T) -> Result<SlackBot<T>, BotError> {
+        let mut bot = SlackBot::new_with_state(self.name, self.token, state);
+        if let Some(prefix) = self.prefix {
+            try!(bot.with_prefix(&prefix[..]));
+        }
+        bot.respond_to_mentions(self.respond_to_mentions);
+        match self.default_command {
+            Some(Some(command_name)) => bot.default_command(&command_name[..]),
+            Some(None) => bot.disable_default_command(),
+            None => {}
+        }
+        bot.explain_channel_restrictions(self.explain_channel_restrictions);
+        bot.handle_subtypes(self.handled_subtypes);
+        if let Some(max_per_minute) = self.rate_limit {
+            bot.rate_limit(max_per_minute);
+        }
+        if let Some(n) = self.max_args {
+            bot.max_args(n);
+        }
+        if let Some(bytes) = self.max_message_len {
+            bot.max_message_len(bytes);
+        }
+        bot.explain_input_limits(self.explain_input_limits);
+        if !self.default_help {
+            bot.disable_default_help();
+        }
+        if let Some(max_retries) = self.auto_reconnect {
+            bot.with_auto_reconnect(max_retries);
+        }
+        Ok(bot)
+    }
+}
@@ -0,0 +1,44 @@
+/// What sort of conversation a message was sent in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelKind {
+    /// A channel anyone on the team can join.
+    Public,
+
+    /// A private channel, which people have to be invited to.
+    Private,
+
+    /// A direct message between one person and the bot.
+    Im,
+
+    /// A direct message between several people.
+    Mpim
+}
+
+impl ChannelKind {
+    /// The kind of channel an id is for, going by how it starts. Slack gives multi-party direct
+    /// messages the same sort of id as private channels, so those look private.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::ChannelKind;
+    /// assert_eq!(ChannelKind::from_id("C024BE91L"), ChannelKind::Public);
+    /// assert_eq!(ChannelKind::from_id("G024BE91L"), ChannelKind::Private);
+    /// assert_eq!(ChannelKind::from_id("D024BE91L"), ChannelKind::Im);
+    /// ```
+    pub fn from_id(channel_id: &str) -> Self {
+        if channel_id.starts_with('D') {
+            ChannelKind::Im
+        } else if channel_id.starts_with('G') {
+            ChannelKind::Private
+        } else {
+            ChannelKind::Public
+        }
+    }
+
+    /// Whether only people who were let in can see what's said, which is true of everything but
+    /// public channels.
+    pub fn is_private(&self) -> bool {
+        *self != ChannelKind::Public
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+use slack::{RtmClient, User, Im, Message, Channel, Group};
+
+use super::identity::BotIdentity;
+
+/// Where the ids of messages sent from worker threads start, so they don't clash with the ones
+/// the RTM client hands out.
+pub const WORKER_MSG_ID_START: isize = 1 << 24;
+
+/// The connection a command context reads from and sends over. On the bot's event loop that's
+/// the RTM client itself. Worker threads can't borrow it, so they get a snapshot instead, which
+/// sends over the same connection.
+#[derive(Clone)]
+pub enum ClientRef<'a> {
+    Rtm(&'a RtmClient),
+    Snapshot(Arc<ClientSnapshot>)
+}
+
+/// What the RTM client knew when a command came in, along with a way to send events back to its
+/// event loop.
+pub struct ClientSnapshot {
+    channels: Vec<Channel>,
+    groups: Vec<Group>,
+    users: Vec<User>,
+    ims: Option<Vec<Im>>,
+    identity: Option<BotIdentity>,
+    // The sending half of a channel can't be shared between threads, only moved
+    outs: Mutex<Option<mpsc::Sender<Message>>>,
+    msg_ids: Arc<AtomicIsize>
+}
+
+impl ClientSnapshot {
+    pub fn new(client: &RtmClient, msg_ids: Arc<AtomicIsize>) -> Self {
+        ClientSnapshot {
+            channels: client.get_channels(),
+            groups: client.get_groups(),
+            users: client.get_users(),
+            ims: client.get_start_ims(),
+            identity: BotIdentity::from_client(client),
+            outs: Mutex::new(client.get_message_sender()),
+            msg_ids: msg_ids
+        }
+    }
+}
+
+impl<'a> ClientRef<'a> {
+    pub fn get_channels(&self) -> Vec<Channel> {
+        match *self {
+            ClientRef::Rtm(client) => client.get_channels(),
+            ClientRef::Snapshot(ref snapshot) => snapshot.channels.clone()
+        }
+    }
+
+    pub fn get_groups(&self) -> Vec<Group> {
+        match *self {
+            ClientRef::Rtm(client) => client.get_groups(),
+            ClientRef::Snapshot(ref snapshot) => snapshot.groups.clone()
+        }
+    }
+
+    pub fn get_users(&self) -> Vec<User> {
+        match *self {
+            ClientRef::Rtm(client) => client.get_users(),
+            ClientRef::Snapshot(ref snapshot) => snapshot.users.clone()
+        }
+    }
+
+    pub fn get_start_ims(&self) -> Option<Vec<Im>> {
+        match *self {
+            ClientRef::Rtm(client) => client.get_start_ims(),
+            ClientRef::Snapshot(ref snapshot) => snapshot.ims.clone()
+        }
+    }
+
+    pub fn get_message_sender(&self) -> Option<mpsc::Sender<Message>> {
+        match *self {
+            ClientRef::Rtm(client) => client.get_message_sender(),
+            ClientRef::Snapshot(ref snapshot) => snapshot.outs.lock().unwrap().clone()
+        }
+    }
+
+    pub fn get_msg_uid(&self) -> isize {
+        match *self {
+            ClientRef::Rtm(client) => client.get_msg_uid(),
+            ClientRef::Snapshot(ref snapshot) => snapshot.msg_ids.fetch_add(1, Ordering::SeqCst)
+        }
+    }
+
+    pub fn identity(&self) -> Option<BotIdentity> {
+        match *self {
+            ClientRef::Rtm(client) => BotIdentity::from_client(client),
+            ClientRef::Snapshot(ref snapshot) => snapshot.identity.clone()
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use super::admin::DEFAULT_ADMIN_COMMAND;
+use super::auth::Authorizer;
+use super::error::BotError;
+use super::messages::Messages;
+use super::presence::Presence;
+use super::sender::DEFAULT_MAX_RESPONSE_LEN;
+
+/// How the kinds of token the bot can use start: bot, user, workspace app and legacy tokens.
+const TOKEN_PREFIXES: &'static [&'static str] = &["xoxb-", "xoxp-", "xoxa-", "xoxs-"];
+
+/// The settings a bot runs with, shared with the event handler while the bot is running.
+pub struct Config {
+    pub token: String,
+    pub web_token: Option<String>,
+    pub check_token_format: bool,
+    pub prefix: String,
+    pub team_prefixes: HashMap<String, String>,
+    pub channel_prefixes: HashMap<String, String>,
+    pub fuzzy_prefix: usize,
+    pub respond_to_mentions: bool,
+    pub threads: Threads,
+    pub default_command: Option<String>,
+    pub admin_command: String,
+    pub admins: Option<Vec<String>>,
+    pub descriptions: HashMap<String, String>,
+    pub aliases: HashMap<String, String>,
+    pub groups: HashSet<String>,
+    pub allowed_channels: HashMap<String, Vec<String>>,
+    pub explain_channel_restrictions: bool,
+    pub authorizers: HashMap<String, Box<Authorizer>>,
+    pub handled_subtypes: Vec<String>,
+    pub event_types: HashSet<String>,
+    pub handler_event_types: HashSet<String>,
+    pub cooldowns: HashMap<String, u64>,
+    pub min_args: HashMap<String, (usize, String)>,
+    pub value_options: HashMap<String, Vec<String>>,
+    pub max_args: Option<usize>,
+    pub max_message_len: Option<usize>,
+    pub max_response_len: usize,
+    pub explain_input_limits: bool,
+    pub messages: Messages,
+    pub presence: Option<Presence>,
+    pub auto_join_on_invite: bool,
+    pub handler_timeout: Option<Duration>
+}
+
+/// Which messages commands are read from, going by whether they're replies in a thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Threads {
+    All,
+    TopLevel,
+    RepliesOnly
+}
+
+impl Threads {
+    /// Whether commands are read from a message. A message that starts a thread isn't a reply
+    /// in it.
+    pub fn allows(&self, ts: &str, thread_ts: Option<&str>) -> bool {
+        let is_reply = thread_ts.map_or(false, |thread_ts| thread_ts != ts);
+        match *self {
+            Threads::All => true,
+            Threads::TopLevel => !is_reply,
+            Threads::RepliesOnly => is_reply
+        }
+    }
+}
+
+impl Config {
+    pub fn new(name: String, token: String) -> Self {
+        Config {
+            token: token,
+            web_token: None,
+            check_token_format: true,
+            prefix: "!".to_owned() + &name[..],
+            team_prefixes: HashMap::new(),
+            channel_prefixes: HashMap::new(),
+            fuzzy_prefix: 0,
+            respond_to_mentions: false,
+            threads: Threads::All,
+            default_command: Some("help".to_owned()),
+            admin_command: DEFAULT_ADMIN_COMMAND.to_owned(),
+            admins: None,
+            descriptions: HashMap::new(),
+            aliases: HashMap::new(),
+            groups: HashSet::new(),
+            allowed_channels: HashMap::new(),
+            explain_channel_restrictions: false,
+            authorizers: HashMap::new(),
+            handled_subtypes: Vec::new(),
+            event_types: vec!["message".to_owned()].into_iter().collect(),
+            handler_event_types: HashSet::new(),
+            cooldowns: HashMap::new(),
+            min_args: HashMap::new(),
+            value_options: HashMap::new(),
+            max_args: None,
+            max_message_len: None,
+            max_response_len: DEFAULT_MAX_RESPONSE_LEN,
+            explain_input_limits: false,
+            messages: Messages::default(),
+            presence: None,
+            auto_join_on_invite: false,
+            handler_timeout: None
+        }
+    }
+
+    /// The prefix commands start with in a channel. A prefix for the channel wins over one for
+    /// its workspace.
+    pub fn prefix(&self, channel_id: &str, team_id: Option<&str>) -> &str {
+        if let Some(prefix) = self.channel_prefixes.get(channel_id) {
+            return &prefix[..];
+        }
+        match team_id.and_then(|team_id| self.team_prefixes.get(team_id)) {
+            Some(prefix) => &prefix[..],
+            None => &self.prefix[..]
+        }
+    }
+
+    /// The token to call the web API with, which is the RTM token unless another was given.
+    pub fn web_token(&self) -> &str {
+        match self.web_token {
+            Some(ref web_token) => &web_token[..],
+            None => &self.token[..]
+        }
+    }
+
+    /// Make sure the tokens could be Slack tokens, so mistakes are caught before logging in.
+    pub fn check_tokens(&self) -> Result<(), BotError> {
+        try!(check_token(&self.token[..], self.check_token_format)
+            .map_err(|msg| BotError::InvalidToken(format!("the token {}", msg))));
+        match self.web_token {
+            Some(ref web_token) => check_token(&web_token[..], self.check_token_format)
+                .map_err(|msg| BotError::InvalidToken(format!("the web API token {}", msg))),
+            None => Ok(())
+        }
+    }
+}
+
+/// Make sure a prefix can start a command. Messages are split on whitespace, so a prefix with
+/// whitespace in it could never match.
+pub fn validate_prefix(prefix: &str) -> Result<(), BotError> {
+    if prefix.chars().any(char::is_whitespace) {
+        return Err(BotError::Config(format!("Command prefix can't contain whitespace: {:?}", prefix)));
+    }
+    Ok(())
+}
+
+/// Why a token can't be right, if it can't. Only its prefix is checked, since Slack doesn't say
+/// what the rest looks like.
+fn check_token(token: &str, check_format: bool) -> Result<(), String> {
+    if token.is_empty() {
+        return Err("is empty".to_owned());
+    }
+    if token.starts_with("https://hooks.slack.com/") {
+        return Err("is a webhook URL rather than a token".to_owned());
+    }
+    if token.chars().any(char::is_whitespace) {
+        return Err("has whitespace in it".to_owned());
+    }
+    if check_format && !TOKEN_PREFIXES.iter().any(|prefix| token.starts_with(prefix)) {
+        return Err(format!("should start with one of {}", TOKEN_PREFIXES.join(", ")));
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Remembers the last few messages the bot has seen, so that ones Slack sends again aren't
+/// handled twice.
+pub struct RecentMessages {
+    capacity: usize,
+    order: VecDeque<String>,
+    seen: HashSet<String>
+}
+
+impl RecentMessages {
+    pub fn new(capacity: usize) -> Self {
+        RecentMessages {
+            capacity: capacity,
+            order: VecDeque::new(),
+            seen: HashSet::new()
+        }
+    }
+
+    /// Change how many messages are remembered, forgetting the oldest ones if there are too many.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.shrink();
+    }
+
+    /// Record that a message was seen, returning whether it had been seen already. Messages are
+    /// told apart by their channel and timestamp, so every event about the same message counts.
+    pub fn seen(&mut self, channel: &str, ts: &str) -> bool {
+        if self.capacity == 0 {
+            return false;
+        }
+
+        let key = format!("{}:{}", channel, ts);
+        if self.seen.contains(&key) {
+            return true;
+        }
+        self.seen.insert(key.clone());
+        self.order.push_back(key);
+        self.shrink();
+        false
+    }
+
+    fn shrink(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecentMessages;
+
+    #[test]
+    fn events_about_the_same_message_are_duplicates() {
+        let mut recent = RecentMessages::new(10);
+        assert!(!recent.seen("C1", "1.0"));
+        assert!(recent.seen("C1", "1.0"));
+        assert!(!recent.seen("C2", "1.0"));
+        assert!(!recent.seen("C1", "2.0"));
+    }
+}
@@ -0,0 +1,38 @@
+use super::sender::Sender;
+
+/// A message in a channel the bot is in that someone edited.
+pub struct MessageEdit {
+    /// What the message said before it was edited.
+    pub previous_text: String,
+
+    /// What the message says now.
+    pub new_text: String
+}
+
+/// A trait implemented by types that handle edits to messages in channels the bot is in.
+///
+/// The sender is the user who edited the message, and its channel and timestamp are those of the
+/// message that was edited, so `respond_in_thread` replies to that message.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{Sender, MessageEdit, MessageEditHandler};
+/// struct AuditLog(Vec<String>);
+///
+/// impl MessageEditHandler for AuditLog {
+///     fn handle(&mut self, sender: &mut Sender, edit: &MessageEdit) {
+///         self.0.push(format!("{} changed {:?} to {:?}", sender.user.name, edit.previous_text, edit.new_text));
+///     }
+/// }
+/// ```
+pub trait MessageEditHandler {
+    /// Handle the edit.
+    fn handle(&mut self, sender: &mut Sender, edit: &MessageEdit);
+}
+
+impl<F> MessageEditHandler for F where F: FnMut(&mut Sender, &MessageEdit) {
+    fn handle(&mut self, sender: &mut Sender, edit: &MessageEdit) {
+        self(sender, edit);
+    }
+}
@@ -0,0 +1,125 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use serde_json;
+
+/// The ways that running a bot or talking to Slack can fail.
+#[derive(Debug)]
+pub enum BotError {
+    /// The bot is set up in a way that can't work, like having an alias for a command it doesn't
+    /// have.
+    Config(String),
+
+    /// The API token can't be right, like when it's empty or is a webhook URL.
+    InvalidToken(String),
+
+    /// Logging in to Slack failed, usually because the API token is wrong or Slack couldn't be
+    /// reached.
+    Login(String),
+
+    /// Talking to Slack failed, like when Slack sends back something unexpected.
+    Slack(String),
+
+    /// Slack couldn't be reached, or the connection to it dropped. Trying again later might work.
+    Network(String),
+
+    /// Slack's Web API said that a method call failed, with the error code it gave (like
+    /// `channel_not_found`).
+    Api {
+        method: String,
+        error: String
+    },
+
+    /// Slack is getting too many calls to a Web API method, and said how long to wait before
+    /// calling it again if it gave a `Retry-After`.
+    RateLimited {
+        method: String,
+        retry_after: Option<Duration>
+    },
+
+    /// Slack sent something that isn't valid JSON.
+    Json(serde_json::Error),
+
+    /// A command handler couldn't do what it was asked, with a message saying why.
+    Command(String),
+
+    /// Saving or loading the bot's state failed.
+    Storage(String)
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BotError::Config(ref msg) => write!(f, "Invalid bot configuration: {}", msg),
+            BotError::InvalidToken(ref msg) => write!(f, "Invalid API token: {}", msg),
+            BotError::Login(ref msg) => write!(f, "{}", msg),
+            BotError::Slack(ref msg) => write!(f, "{}", msg),
+            BotError::Network(ref msg) => write!(f, "Couldn't reach Slack: {}", msg),
+            BotError::Api { ref method, ref error } => write!(f, "{} failed: {}", method, error),
+            BotError::RateLimited { ref method, retry_after: Some(retry_after) } =>
+                write!(f, "{} was rate limited for {}s", method, retry_after.as_secs()),
+            BotError::RateLimited { ref method, retry_after: None } => write!(f, "{} was rate limited", method),
+            BotError::Json(ref err) => write!(f, "Invalid JSON from Slack: {}", err),
+            BotError::Command(ref msg) => write!(f, "{}", msg),
+            BotError::Storage(ref msg) => write!(f, "Couldn't save or load the bot's state: {}", msg)
+        }
+    }
+}
+
+impl Error for BotError {
+    fn description(&self) -> &str {
+        match *self {
+            BotError::Config(_) => "invalid bot configuration",
+            BotError::InvalidToken(_) => "invalid API token",
+            BotError::Login(_) => "couldn't log in to Slack",
+            BotError::Slack(_) => "couldn't talk to Slack",
+            BotError::Network(_) => "couldn't reach Slack",
+            BotError::Api { .. } => "Slack API call failed",
+            BotError::RateLimited { .. } => "rate limited by Slack",
+            BotError::Json(_) => "invalid JSON from Slack",
+            BotError::Command(_) => "command failed",
+            BotError::Storage(_) => "couldn't save or load the bot's state"
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            BotError::Json(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl BotError {
+    /// Whether the error is likely to go away by itself, like being rate limited or losing the
+    /// connection, so that trying again later might work. Other errors will keep happening until
+    /// something changes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::BotError;
+    /// let err = BotError::Api { method: "chat.postMessage".to_owned(), error: "ratelimited".to_owned() };
+    /// assert!(err.is_transient());
+    ///
+    /// let err = BotError::Api { method: "chat.postMessage".to_owned(), error: "channel_not_found".to_owned() };
+    /// assert!(!err.is_transient());
+    /// ```
+    pub fn is_transient(&self) -> bool {
+        match *self {
+            BotError::Network(_) | BotError::RateLimited { .. } => true,
+            BotError::Api { ref error, .. } => TRANSIENT_API_ERRORS.contains(&&error[..]),
+            _ => false
+        }
+    }
+}
+
+/// Web API error codes that Slack gives when it's busy rather than when a call is wrong.
+const TRANSIENT_API_ERRORS: &'static [&'static str] = &["ratelimited", "request_timeout", "service_unavailable", "internal_error"];
+
+impl From<serde_json::Error> for BotError {
+    fn from(err: serde_json::Error) -> Self {
+        BotError::Json(err)
+    }
+}
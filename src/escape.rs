@@ -0,0 +1,55 @@
+/// Escape the characters Slack treats as formatting control characters (`&`, `<` and `>`), so
+/// text shows up exactly as written.
+///
+/// The bot does this to the messages it sends, so this is only needed when building text for
+/// something like `Sender::respond_in_channel_raw` that's sent as is.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::escape;
+/// assert_eq!(escape("salt & pepper"), "salt &amp; pepper");
+/// assert_eq!(escape("1 < 2"), "1 &lt; 2");
+/// assert_eq!(escape("2 > 1"), "2 &gt; 1");
+/// assert_eq!(escape("<@U12345>"), "&lt;@U12345&gt;");
+/// ```
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Undo Slack's escaping of the text of a message it's sent us.
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, unescape};
+
+    #[test]
+    fn escapes_ampersands() {
+        assert_eq!(escape("salt & pepper"), "salt &amp; pepper");
+    }
+
+    #[test]
+    fn escapes_less_than() {
+        assert_eq!(escape("1 < 2"), "1 &lt; 2");
+    }
+
+    #[test]
+    fn escapes_greater_than() {
+        assert_eq!(escape("2 > 1"), "2 &gt; 1");
+    }
+
+    // Text is escaped so it shows up as written, so text that looks escaped is escaped again
+    #[test]
+    fn escapes_already_escaped_text_again() {
+        assert_eq!(escape("&amp; &lt;"), "&amp;amp; &amp;lt;");
+        assert_eq!(unescape(&escape("&amp; &lt;")[..]), "&amp; &lt;");
+    }
+
+    #[test]
+    fn unescapes_what_escape_escaped() {
+        assert_eq!(unescape("&lt;@U12345&gt; &amp; co"), "<@U12345> & co");
+    }
+}
@@ -0,0 +1,234 @@
+use serde_json::Value;
+
+/// Just the type of an event from Slack, which is all that's read of events the bot skips.
+#[derive(Deserialize, Debug)]
+pub struct TypedEvent {
+    #[serde(rename = "type")]
+    pub ty: Option<String>
+}
+
+/// A `message` event from Slack. Each field is only there if Slack sent it, since which fields
+/// are sent depends on the kind of message.
+#[derive(Deserialize, Debug)]
+pub struct MessageEvent {
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub text: Option<String>,
+    pub user: Option<String>,
+    pub channel: Option<String>,
+    pub ts: Option<String>,
+    pub thread_ts: Option<String>,
+    pub subtype: Option<String>,
+
+    /// The workspace the message was sent in, which Enterprise Grid sends along with its events.
+    pub team: Option<String>,
+
+    /// Files that were shared with the message.
+    pub files: Option<Vec<FileInfo>>
+}
+
+/// A file in a message event.
+#[derive(Clone, Deserialize, Debug)]
+pub struct FileInfo {
+    pub id: String,
+    pub name: Option<String>,
+    pub mimetype: Option<String>,
+    pub size: Option<u64>,
+    pub url_private: Option<String>
+}
+
+/// A `message` event with the `message_changed` subtype, which Slack sends when a message is
+/// edited, or when it changes the message itself, like to unfurl a link.
+#[derive(Deserialize, Debug)]
+pub struct MessageChangedEvent {
+    pub channel: String,
+    pub message: ChangedMessage,
+    pub previous_message: ChangedMessage
+}
+
+/// The message in a `message_changed` event, before or after it changed.
+#[derive(Deserialize, Debug)]
+pub struct ChangedMessage {
+    pub text: Option<String>,
+    pub ts: String,
+    pub thread_ts: Option<String>,
+
+    /// Who edited the message, if someone did.
+    pub edited: Option<Edited>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Edited {
+    pub user: String
+}
+
+/// A `reaction_added` or `reaction_removed` event from Slack.
+#[derive(Deserialize, Debug)]
+pub struct ReactionEvent {
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub user: String,
+    pub reaction: String,
+    pub item: ReactionItem
+}
+
+/// What a reaction was added to or removed from. Only messages have a channel and timestamp.
+#[derive(Deserialize, Debug)]
+pub struct ReactionItem {
+    pub channel: Option<String>,
+    pub ts: Option<String>
+}
+
+/// A `member_joined_channel` or `member_left_channel` event from Slack.
+#[derive(Deserialize, Debug)]
+pub struct MemberChannelEvent {
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub user: String,
+    pub channel: String
+}
+
+/// A `presence_change` event from Slack, about one user or several.
+#[derive(Deserialize, Debug)]
+pub struct PresenceChangeEvent {
+    pub user: Option<String>,
+    pub users: Option<Vec<String>>,
+    pub presence: String
+}
+
+/// A `user_change` or `team_join` event from Slack. The user is left as JSON, since Slack sends
+/// far more about them than we keep.
+#[derive(Deserialize, Debug)]
+pub struct UserEvent {
+    pub user: Value
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::{MessageEvent, MessageChangedEvent, ReactionEvent, PresenceChangeEvent};
+
+    #[test]
+    fn parses_a_message() {
+        let json = r#"{
+            "type": "message",
+            "channel": "C2147483705",
+            "user": "U2147483697",
+            "text": "!bot echo Hello world",
+            "ts": "1355517523.000005",
+            "team": "T061EG9R6",
+            "files": [{
+                "id": "F0S43PZDF",
+                "name": "deploy.log",
+                "mimetype": "text/plain",
+                "size": 1024,
+                "url_private": "https://files.slack.com/files-pri/T061EG9R6-F0S43PZDF/deploy.log"
+            }]
+        }"#;
+        let event: MessageEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.ty, "message");
+        assert_eq!(event.channel, Some("C2147483705".to_owned()));
+        assert_eq!(event.user, Some("U2147483697".to_owned()));
+        assert_eq!(event.text, Some("!bot echo Hello world".to_owned()));
+        assert_eq!(event.ts, Some("1355517523.000005".to_owned()));
+        assert_eq!(event.team, Some("T061EG9R6".to_owned()));
+        let files = event.files.unwrap();
+        assert_eq!(files[0].id, "F0S43PZDF");
+        assert_eq!(files[0].size, Some(1024));
+    }
+
+    #[test]
+    fn parses_a_message_without_optional_fields() {
+        let json = r#"{"type": "message", "subtype": "channel_join", "ts": "1358877455.000010"}"#;
+        let event: MessageEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.subtype, Some("channel_join".to_owned()));
+        assert_eq!(event.text, None);
+        assert_eq!(event.user, None);
+        assert_eq!(event.channel, None);
+        assert_eq!(event.thread_ts, None);
+        assert_eq!(event.team, None);
+        assert!(event.files.is_none());
+    }
+
+    #[test]
+    fn parses_a_message_changed_event() {
+        let json = r#"{
+            "type": "message",
+            "subtype": "message_changed",
+            "hidden": true,
+            "channel": "C2147483705",
+            "ts": "1358878755.000001",
+            "message": {
+                "type": "message",
+                "user": "U2147483697",
+                "text": "Hello, world!",
+                "ts": "1355517523.000005",
+                "edited": {"user": "U2147483697", "ts": "1358878755.000001"}
+            },
+            "previous_message": {
+                "type": "message",
+                "user": "U2147483697",
+                "text": "Helo, world!",
+                "ts": "1355517523.000005"
+            }
+        }"#;
+        let event: MessageChangedEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.channel, "C2147483705");
+        assert_eq!(event.message.text, Some("Hello, world!".to_owned()));
+        assert_eq!(event.message.edited.unwrap().user, "U2147483697");
+        assert_eq!(event.previous_message.text, Some("Helo, world!".to_owned()));
+        assert!(event.previous_message.edited.is_none());
+        assert_eq!(event.message.thread_ts, None);
+    }
+
+    #[test]
+    fn parses_a_reaction_to_a_message() {
+        let json = r#"{
+            "type": "reaction_added",
+            "user": "U024BE7LH",
+            "reaction": "thumbsup",
+            "item_user": "U0G9QF9C6",
+            "item": {"type": "message", "channel": "C0G9QF9GZ", "ts": "1360782400.498405"},
+            "event_ts": "1360782804.083113"
+        }"#;
+        let event: ReactionEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.ty, "reaction_added");
+        assert_eq!(event.user, "U024BE7LH");
+        assert_eq!(event.reaction, "thumbsup");
+        assert_eq!(event.item.channel, Some("C0G9QF9GZ".to_owned()));
+        assert_eq!(event.item.ts, Some("1360782400.498405".to_owned()));
+    }
+
+    #[test]
+    fn parses_a_reaction_to_a_file() {
+        let json = r#"{
+            "type": "reaction_added",
+            "user": "U024BE7LH",
+            "reaction": "thumbsup",
+            "item": {"type": "file", "file": "F0HS27V1Z"},
+            "event_ts": "1360782804.083113"
+        }"#;
+        let event: ReactionEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.item.channel, None);
+        assert_eq!(event.item.ts, None);
+    }
+
+    #[test]
+    fn parses_a_presence_change_for_one_user() {
+        let json = r#"{"type": "presence_change", "user": "U024BE7LH", "presence": "away"}"#;
+        let event: PresenceChangeEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.user, Some("U024BE7LH".to_owned()));
+        assert!(event.users.is_none());
+        assert_eq!(event.presence, "away");
+    }
+
+    #[test]
+    fn parses_a_presence_change_for_several_users() {
+        let json = r#"{"type": "presence_change", "users": ["U024BE7LH", "U012EA2U1"], "presence": "active"}"#;
+        let event: PresenceChangeEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.user, None);
+        assert_eq!(event.users, Some(vec!["U024BE7LH".to_owned(), "U012EA2U1".to_owned()]));
+        assert_eq!(event.presence, "active");
+    }
+}
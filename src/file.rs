@@ -0,0 +1,75 @@
+use super::api;
+use super::error::BotError;
+use super::events::FileInfo;
+
+/// A file that was shared along with a message.
+#[derive(Clone, Debug)]
+pub struct SlackFile {
+    /// Slack's id for the file.
+    pub id: String,
+
+    /// The file's name, like `report.csv`.
+    pub name: String,
+
+    /// The file's type, like `text/csv`, if Slack knows it.
+    pub mimetype: Option<String>,
+
+    /// How big the file is, in bytes.
+    pub size: Option<u64>,
+
+    /// Where the file can be downloaded from, with the bot's token.
+    pub url_private: String,
+
+    token: String
+}
+
+impl SlackFile {
+    pub fn new<A, B, C, D>(id: A, name: B, url_private: C, token: D) -> Self
+        where A: Into<String>, B: Into<String>, C: Into<String>, D: Into<String> {
+
+        SlackFile {
+            id: id.into(),
+            name: name.into(),
+            mimetype: None,
+            size: None,
+            url_private: url_private.into(),
+            token: token.into()
+        }
+    }
+
+    /// A file from a message event, if it can be downloaded.
+    pub fn from_event(file: FileInfo, token: &str) -> Option<Self> {
+        let url_private = match file.url_private {
+            Some(url_private) => url_private,
+            None => return None
+        };
+        Some(SlackFile {
+            name: file.name.unwrap_or(file.id.clone()),
+            id: file.id,
+            mimetype: file.mimetype,
+            size: file.size,
+            url_private: url_private,
+            token: token.to_owned()
+        })
+    }
+
+    /// Download the file's contents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("lines", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     for file in ctx.attached_files() {
+    ///         if let Ok(content) = file.download() {
+    ///             let lines = content.iter().filter(|&&byte| byte == b'\n').count();
+    ///             ctx.respond_in_channel(format!("{} has {} lines", file.name, lines));
+    ///         }
+    ///     }
+    /// }));
+    /// ```
+    pub fn download(&self) -> Result<Vec<u8>, BotError> {
+        api::download(&self.token[..], &self.url_private[..])
+    }
+}
@@ -0,0 +1,67 @@
+use super::{CommandHandler, SlackBot};
+
+/// A set of commands that start with the same word, like `ops deploy` and `ops rollback`, and
+/// share the same restrictions.
+///
+/// Commands in a group are registered under their full name (`ops deploy`), so that's the name
+/// to use with `off` or `alias`, and the one `help` shows.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{SlackBot, CommandContext};
+/// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// let mut ops = my_bot.group("ops").authorized(&["U12345"]);
+/// ops.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///     ctx.respond_in_channel("Deploying...");
+/// }));
+/// ops.on("rollback", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///     ctx.respond_in_channel("Rolling back...");
+/// }));
+/// ```
+pub struct CommandGroup<'a, T: 'a> {
+    bot: &'a mut SlackBot<T>,
+    name: String,
+    allowed_users: Option<Vec<String>>,
+    allowed_channels: Option<Vec<String>>
+}
+
+impl<'a, T> CommandGroup<'a, T> {
+    pub fn new<S: Into<String>>(bot: &'a mut SlackBot<T>, name: S) -> Self {
+        let name = name.into();
+        bot.config.groups.insert(name.clone());
+        CommandGroup {
+            bot: bot,
+            name: name,
+            allowed_users: None,
+            allowed_channels: None
+        }
+    }
+
+    /// Only let some users run the group's commands, given by id or by name, like
+    /// `SlackBot::on_authorized`.
+    pub fn authorized(mut self, users: &[&str]) -> Self {
+        self.allowed_users = Some(users.iter().map(|&user| user.to_owned()).collect());
+        self
+    }
+
+    /// Only let the group's commands be used in some channels, given by name or by id, like
+    /// `SlackBot::on_in_channels`.
+    pub fn in_channels(mut self, channels: &[&str]) -> Self {
+        self.allowed_channels = Some(channels.iter().map(|&channel| channel.to_owned()).collect());
+        self
+    }
+
+    /// Tell your bot what to do when it sees one of the group's commands, with the restrictions
+    /// the group has so far.
+    pub fn on<S: Into<String>>(&mut self, command_name: S, handler: Box<CommandHandler + Send>) {
+        let command_name = format!("{} {}", self.name, command_name.into());
+        if let Some(ref users) = self.allowed_users {
+            self.bot.config.authorizers.insert(command_name.clone(), Box::new(users.clone()));
+        }
+        if let Some(ref channels) = self.allowed_channels {
+            self.bot.config.allowed_channels.insert(command_name.clone(), channels.clone());
+        }
+        self.bot.on(command_name, handler);
+    }
+}
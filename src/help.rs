@@ -0,0 +1,102 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::CommandHandler;
+use super::messages::{fill, Messages};
+use super::sender::CommandContext;
+
+/// The built-in `help` command, which lists the commands a bot knows about.
+pub struct HelpCommandHandler {
+    /// Looked at each time, so commands added while the bot is running show up.
+    command_names: Arc<Mutex<Vec<String>>>,
+    descriptions: HashMap<String, String>,
+    header: String
+}
+
+impl HelpCommandHandler {
+    pub fn new(command_names: Arc<Mutex<Vec<String>>>, descriptions: &HashMap<String, String>, header: &str) -> Self {
+        HelpCommandHandler {
+            command_names: command_names,
+            descriptions: descriptions.clone(),
+            header: header.to_owned()
+        }
+    }
+}
+
+impl CommandHandler for HelpCommandHandler {
+    fn handle(&mut self, ctx: &mut CommandContext, _: &Vec<String>) {
+        let lines = self.command_names.lock().unwrap().iter()
+            .map(|name| match self.descriptions.get(name) {
+                Some(description) => format!("`{}` — {}", name, description),
+                None => format!("`{}`", name)
+            })
+            .collect::<Vec<_>>();
+        let _ = ctx.respond_in_channel(format!("{}\n{}", self.header, lines.join("\n")));
+    }
+}
+
+/// The built-in handler for commands that don't exist, which suggests a command with a similar
+/// name or points people towards `help`.
+pub struct UnknownCommandHandler {
+    prefix: String,
+    has_help: bool,
+
+    /// The commands to suggest from. Empty when suggestions are turned off.
+    suggestions: Vec<String>,
+    messages: Messages
+}
+
+impl UnknownCommandHandler {
+    pub fn new<S: Into<String>>(prefix: S, has_help: bool, suggestions: Vec<String>, messages: &Messages) -> Self {
+        UnknownCommandHandler {
+            prefix: prefix.into(),
+            has_help: has_help,
+            suggestions: suggestions,
+            messages: messages.clone()
+        }
+    }
+
+    /// The command with the name closest to the one that was attempted, if any are close enough
+    /// to be a typo.
+    fn suggestion(&self, command: &str) -> Option<&str> {
+        // Short commands can be a couple of edits from almost anything, so they need to be closer
+        let length = command.chars().count();
+        let max_distance = cmp::max(2, length / 3);
+        self.suggestions.iter()
+            .map(|name| (edit_distance(command, &name[..]), name))
+            .filter(|&(distance, _)| distance <= max_distance && distance < length)
+            .min_by_key(|&(distance, _)| distance)
+            .map(|(_, name)| &name[..])
+    }
+}
+
+impl CommandHandler for UnknownCommandHandler {
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) {
+        let command = args.first().map(|c| &c[..]).unwrap_or("");
+        let message = if let Some(suggestion) = self.suggestion(command) {
+            fill(&self.messages.unknown_command_suggestion[..], &[("command", command), ("suggestion", suggestion)])
+        } else if self.has_help {
+            fill(&self.messages.unknown_command_help[..], &[("command", command), ("prefix", &self.prefix[..])])
+        } else {
+            fill(&self.messages.unknown_command[..], &[("command", command)])
+        };
+        let _ = ctx.respond_in_channel(message);
+    }
+}
+
+/// The Levenshtein distance between two strings: how many characters have to be added, removed
+/// or changed to turn one into the other.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..b.len() + 1).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == b_char { 0 } else { 1 };
+            current.push(cmp::min(substitution, cmp::min(previous[j + 1], current[j]) + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,33 @@
+use super::error::BotError;
+
+/// The picture a message is posted with when the bot posts under another name. See
+/// `CommandContext::respond_as`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Icon {
+    /// An emoji, by name with or without colons, like `rocket` or `:rocket:`.
+    Emoji(String),
+
+    /// An image on the web.
+    Url(String)
+}
+
+impl Icon {
+    /// The `chat.postMessage` parameter for the icon, or an error if Slack wouldn't take it.
+    pub fn param(&self) -> Result<(&'static str, String), BotError> {
+        match *self {
+            Icon::Emoji(ref name) => {
+                let name = name.trim_matches(':');
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Err(BotError::Command(format!("`{}` isn't an emoji name", name)));
+                }
+                Ok(("icon_emoji", format!(":{}:", name)))
+            },
+            Icon::Url(ref url) => {
+                if !url.starts_with("https://") && !url.starts_with("http://") {
+                    return Err(BotError::Command(format!("`{}` isn't a web address", url)));
+                }
+                Ok(("icon_url", url.clone()))
+            }
+        }
+    }
+}
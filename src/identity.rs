@@ -0,0 +1,32 @@
+use slack::RtmClient;
+
+/// Who the bot is logged in as, and the team it's logged in to.
+#[derive(Clone, Debug)]
+pub struct BotIdentity {
+    /// The bot's user id, used to mention it as `<@U12345>`.
+    pub user_id: String,
+
+    /// The bot's user name.
+    pub name: String,
+
+    /// The id of the team the bot is in.
+    pub team_id: String,
+
+    /// The team's domain, as in `domain.slack.com`.
+    pub team_domain: String
+}
+
+impl BotIdentity {
+    /// The identity Slack gave the client when it logged in, if it has logged in.
+    pub fn from_client(client: &RtmClient) -> Option<Self> {
+        match (client.get_id(), client.get_name(), client.get_team()) {
+            (Some(user_id), Some(name), Some(team)) => Some(BotIdentity {
+                user_id: user_id,
+                name: name,
+                team_id: team.id,
+                team_domain: team.domain
+            }),
+            _ => None
+        }
+    }
+}
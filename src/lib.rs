@@ -9,40 +9,178 @@
 //! ```rust,no_run
 //! extern crate slackbot;
 //!
-//! use slackbot::{SlackBot, Sender};
+//! use slackbot::{SlackBot, CommandContext};
 //!
 //! fn main() {
 //!     let mut echo_bot = SlackBot::new("bot", "BOT_API_TOKEN");
 //!
-//!     echo_bot.on("echo", Box::new(|sender: &mut Sender, args: &Vec<String>| {
+//!     echo_bot.on("echo", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
 //!         if args.len() > 0 {
-//!             sender.respond_in_channel(args.join(" ")).unwrap();
+//!             ctx.respond_in_channel(args.join(" "));
 //!         } else {
-//!             sender.respond_in_channel("echo echo echo").unwrap();
+//!             ctx.respond_in_channel("echo echo echo");
 //!         }
 //!     }));
 //!
 //!     echo_bot.run().unwrap();
 //! }
 //! ```
+//!
+//! # Logging
+//!
+//! slackbot logs what it's doing with the `log` crate, so use any logger to see it. Commands are
+//! logged at `info`, things the bot skips at `debug`, and every event from Slack at `trace`.
 
 extern crate slack;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
 extern crate serde_json;
+extern crate hyper;
+extern crate futures;
+extern crate futures_cpupool;
+extern crate regex;
+#[macro_use]
+extern crate log;
 
-use std::collections::HashMap;
-use slack::RtmClient;
+use std::cmp;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use slack::{EventHandler, RtmClient};
+use futures::{Future, IntoFuture};
+use futures_cpupool::CpuPool;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 
+mod action;
+mod admin;
+mod api;
+mod args;
+mod async_sender;
+mod attachment;
+mod auth;
+mod blocks;
+mod builder;
+mod channel_kind;
+mod client;
+mod config;
+mod dedupe;
+mod edit;
+mod error;
+mod escape;
 mod event_handler;
+mod events;
+mod file;
+mod group;
+mod handlers;
+mod help;
+mod icon;
+mod identity;
+mod lifecycle;
+mod lookup;
+mod matcher;
+mod member;
+mod message;
+mod messages;
+mod metrics;
+mod middleware;
+mod outbox;
+mod presence;
+mod random;
+mod rate_limit;
+mod reaction;
+mod registry;
+mod reply;
+mod report;
+mod router;
+mod schedule;
 mod sender;
+mod shutdown;
+mod store;
+mod tokenize;
+mod watchdog;
+mod worker;
 
+use admin::AdminCommandHandler;
+use config::{Config, Threads};
+use dedupe::RecentMessages;
 use event_handler::SlackBotEventHandler;
-pub use sender::Sender;
+use handlers::Handlers;
+use help::{HelpCommandHandler, UnknownCommandHandler};
+use rate_limit::RateLimiter;
+use reply::Replying;
+use report::Reported;
+use schedule::{CronSchedule, Job, Scheduler};
+use sender::Recording;
+use shutdown::ShutdownState;
+use store::Persistence;
+use worker::Workers;
+pub use action::{Action, ActionHandle, ActionHandler};
+pub use args::{Args, ParsedArgs};
+pub use async_sender::AsyncSender;
+pub use attachment::Attachment;
+pub use auth::Authorizer;
+pub use blocks::{Block, Text, Section, Context, Actions, Button};
+pub use builder::SlackBotBuilder;
+pub use channel_kind::ChannelKind;
+pub use edit::{MessageEdit, MessageEditHandler};
+pub use error::BotError;
+pub use escape::escape;
+pub use file::SlackFile;
+pub use group::CommandGroup;
+pub use icon::Icon;
+pub use identity::BotIdentity;
+pub use lifecycle::BotEvent;
+pub use matcher::MatchHandler;
+pub use member::{MemberEvent, MemberEventHandler};
+pub use message::MessageHandler;
+pub use messages::Messages;
+pub use metrics::Metrics;
+pub use middleware::Middleware;
+pub use outbox::Queued;
+pub use presence::Presence;
+pub use reaction::{Reaction, ReactionHandler};
+pub use registry::CommandRegistry;
+pub use reply::CommandReply;
+pub use report::ErrorReporter;
+pub use router::CommandRouter;
+pub use schedule::{ChannelSender, ScheduledTask};
+pub use sender::{CommandContext, RecordedMessage, Sender, SentMessage};
+pub use shutdown::ShutdownHandle;
+pub use store::{StateStore, JsonFileStore};
+pub use regex::Regex;
+
+const INITIAL_BACKOFF_SECS: u64 = 1;
+const MAX_BACKOFF_SECS: u64 = 64;
+const DEFAULT_DEDUPE_WINDOW: usize = 100;
+const DEFAULT_TOKEN_VAR: &'static str = "SLACK_API_TOKEN";
+
+/// Which of the default handlers a bot was given when it started running.
+struct DefaultHandlers {
+    help: bool,
+    unknown: bool,
+    admin: bool
+}
 
 /// The bot that handles commands and communication with Slack.
-pub struct SlackBot {
-    name: String,
-    token: String,
-    handlers: HashMap<String, Box<CommandHandler>>
+///
+/// A bot can keep some state of type `T` that's shared by all of its stateful command handlers.
+/// Bots without state use `()`.
+pub struct SlackBot<T = ()> {
+    config: Config,
+    handlers: Handlers<T>,
+    default_help: bool,
+    suggest_commands: bool,
+    auto_reconnect: bool,
+    max_retries: Option<usize>,
+    shutdown: Arc<Mutex<ShutdownState>>,
+    recent_messages: RecentMessages,
+    jobs: Vec<Job>,
+    state: T
 }
 
 impl SlackBot {
@@ -58,90 +196,1804 @@ impl SlackBot {
     pub fn new<A,B>(name: A, token: B) -> Self
         where A: Into<String>, B: Into<String> {
 
+        SlackBot::new_with_state(name, token, ())
+    }
+
+    /// Create a new bot with the API token in the `SLACK_API_TOKEN` environment variable.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use slackbot::SlackBot;
+    ///
+    /// let mut my_bot = SlackBot::from_env("bot").unwrap();
+    /// ```
+    pub fn from_env<S: Into<String>>(name: S) -> Result<Self, BotError> {
+        SlackBot::from_env_var(name, DEFAULT_TOKEN_VAR)
+    }
+
+    /// Create a new bot with the API token in an environment variable. Fails if the variable
+    /// isn't set, or is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use slackbot::SlackBot;
+    ///
+    /// assert!(SlackBot::from_env_var("bot", "NO_SUCH_TOKEN_VARIABLE").is_err());
+    /// ```
+    pub fn from_env_var<S: Into<String>>(name: S, var: &str) -> Result<Self, BotError> {
+        match env::var(var) {
+            Ok(ref token) if token.is_empty() => Err(BotError::Config(format!("${} is empty", var))),
+            Ok(token) => Ok(SlackBot::new(name, token)),
+            Err(env::VarError::NotPresent) => Err(BotError::Config(format!("${} isn't set", var))),
+            Err(env::VarError::NotUnicode(_)) => Err(BotError::Config(format!("${} isn't valid unicode", var)))
+        }
+    }
+
+    /// Start setting up a bot with a builder, for when it has a lot of options.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use slackbot::SlackBot;
+    ///
+    /// let my_bot = SlackBot::builder("bot", "YOUR_API_TOKEN")
+    ///     .respond_to_mentions(true)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder<A,B>(name: A, token: B) -> SlackBotBuilder
+        where A: Into<String>, B: Into<String> {
+
+        SlackBotBuilder::new(name, token)
+    }
+}
+
+impl<T> SlackBot<T> {
+    /// Create a new bot that keeps track of some state, which is given to its stateful command
+    /// handlers each time they handle a command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use slackbot::SlackBot;
+    ///
+    /// let mut counter_bot = SlackBot::new_with_state("bot", "YOUR_API_TOKEN", 0);
+    /// ```
+    pub fn new_with_state<A,B>(name: A, token: B, state: T) -> Self
+        where A: Into<String>, B: Into<String> {
+
         SlackBot {
-            name: name.into(),
-            token: token.into(),
-            handlers: HashMap::new()
+            config: Config::new(name.into(), token.into()),
+            handlers: Handlers::new(),
+            default_help: true,
+            suggest_commands: true,
+            auto_reconnect: false,
+            max_retries: None,
+            shutdown: ShutdownState::new(),
+            recent_messages: RecentMessages::new(DEFAULT_DEDUPE_WINDOW),
+            jobs: Vec::new(),
+            state: state
         }
     }
 
+    /// The bot's state.
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+
+    /// The bot's state, for changing it while the bot isn't running.
+    pub fn state_mut(&mut self) -> &mut T {
+        &mut self.state
+    }
+
+    /// Change what your bot listens for at the start of a message. By default, this is `!` followed
+    /// by the bot's name (e.g. `!bot`).
+    ///
+    /// An empty prefix means that every message is treated as a command attempt. Prefixes can't
+    /// contain whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.with_prefix(".").unwrap();
+    ///
+    /// assert!(my_bot.with_prefix("hey bot").is_err());
+    /// ```
+    pub fn with_prefix(&mut self, prefix: &str) -> Result<(), BotError> {
+        try!(config::validate_prefix(prefix));
+        self.config.prefix = prefix.to_owned();
+        Ok(())
+    }
+
+    /// Use a different prefix in one workspace of an Enterprise Grid, given by its team id.
+    /// Messages from other workspaces keep using the bot's usual prefix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.team_prefix("T12345", "!ops").unwrap();
+    /// ```
+    pub fn team_prefix(&mut self, team_id: &str, prefix: &str) -> Result<(), BotError> {
+        try!(config::validate_prefix(prefix));
+        self.config.team_prefixes.insert(team_id.to_owned(), prefix.to_owned());
+        Ok(())
+    }
+
+    /// Use a different prefix in one channel, given by its id. Other channels keep using the
+    /// bot's usual prefix, or their workspace's.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.with_prefix("!").unwrap();
+    /// my_bot.prefix_for_channel("C12345", ".").unwrap();
+    /// ```
+    pub fn prefix_for_channel(&mut self, channel_id: &str, prefix: &str) -> Result<(), BotError> {
+        try!(config::validate_prefix(prefix));
+        self.config.channel_prefixes.insert(channel_id.to_owned(), prefix.to_owned());
+        Ok(())
+    }
+
+    /// Let commands through when the prefix has a typo in it, like `!bto echo` for a bot with the
+    /// prefix `!bot`, as long as it's no more than `max_distance` characters off (counting each
+    /// character added, removed or changed). This is off by default, since it can catch messages
+    /// that weren't meant for the bot; 0 turns it off again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.fuzzy_prefix(2);
+    /// ```
+    pub fn fuzzy_prefix(&mut self, max_distance: usize) {
+        self.config.fuzzy_prefix = max_distance;
+    }
+
     /// Tell your bot what to do when it sees a command.
     ///
     /// The handler can be your own type that implements `CommandHandler`, but most simple cases
-    /// can be covered by a simple closure.
+    /// can be covered by a simple closure. Handlers have to be `Send`, so that commands can be
+    /// changed from other threads with a `CommandRegistry`.
+    ///
+    /// A `*` in the name makes a wildcard command, like `git-*`, that handles any command it
+    /// matches that has no handler of its own. The part the `*` matched comes first in the args.
     ///
     /// # Examples
     ///
     /// With a simple closure:
     ///
     /// ```
-    /// # use slackbot::{SlackBot, Sender};
+    /// # use slackbot::{SlackBot, CommandContext};
     /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
-    /// my_bot.on("say-hello", Box::new(|sender: &mut Sender, args: &Vec<String>| {
-    ///     sender.respond_in_channel("Hello, world!");
+    /// my_bot.on("say-hello", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Hello, world!");
     /// }));
     /// ```
     ///
     /// With an implemented CommandHandler:
     ///
     /// ```
-    /// # use slackbot::{SlackBot, Sender, CommandHandler};
+    /// # use slackbot::{SlackBot, CommandContext, CommandHandler};
     /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
     /// struct SayHelloCommandHandler;
     ///
     /// impl CommandHandler for SayHelloCommandHandler {
-    ///     fn handle(&mut self, sender: &mut Sender, args: &Vec<String>) {
-    ///         sender.respond_in_channel("Hello, world!");
+    ///     fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) {
+    ///         ctx.respond_in_channel("Hello, world!");
     ///     }
     /// }
     ///
     /// my_bot.on("say-hello", Box::new(SayHelloCommandHandler));
     /// ```
-    pub fn on<S: Into<String>>(&mut self, command_name: S, handler: Box<CommandHandler>) {
-        self.handlers.insert(command_name.into(), handler);
+    ///
+    /// With a wildcard:
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// // `!bot git-log` is handled with `log` as the first arg
+    /// my_bot.on("git-*", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel(format!("Running git {}", args[0]));
+    /// }));
+    /// ```
+    pub fn on<S: Into<String>>(&mut self, command_name: S, handler: Box<CommandHandler + Send>) {
+        self.on_stateful(command_name, Box::new(Stateless(handler)));
     }
 
-    /// Tell your bot to start pulling its weight!
+    /// Start a group of commands that are used after a shared first word, like `!bot ops deploy`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let mut ops = my_bot.group("ops").in_channels(&["#ops"]);
+    /// ops.on("restart", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Restarting...");
+    /// }));
+    /// ```
+    pub fn group<S: Into<String>>(&mut self, name: S) -> CommandGroup<T> {
+        CommandGroup::new(self, name)
+    }
+
+    /// Tell your bot what to do when it sees a command, with a handler that can use the bot's
+    /// state. Like with `on`, the handler has to be `Send`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// let mut counter_bot = SlackBot::new_with_state("bot", "YOUR_API_TOKEN", 0);
+    ///
+    /// counter_bot.on_stateful("count", Box::new(|count: &mut u32, ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     *count += 1;
+    ///     ctx.respond_in_channel(format!("I've counted to {}", count));
+    /// }));
+    /// ```
+    pub fn on_stateful<S: Into<String>>(&mut self, command_name: S, handler: Box<StatefulCommandHandler<T> + Send>) {
+        let command_name = command_name.into();
+        self.handlers.async_commands.remove(&command_name);
+        self.handlers.registry().on_stateful(command_name, handler);
+    }
+
+    /// Tell your bot what to do when it sees a command, with a handler that can fail. Errors it
+    /// returns go to the bot's `ErrorReporter`, or are logged if it doesn't have one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, BotError};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_try("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     let environment = try!(args.first().ok_or(BotError::Command("Deploy where?".to_owned())));
+    ///     try!(ctx.respond_in_channel(format!("Deploying to {}...", environment)).wait());
+    ///     Ok(())
+    /// }));
+    /// ```
+    pub fn on_try<S: Into<String>>(&mut self, command_name: S, handler: Box<TryCommandHandler + Send>) {
+        let command_name = command_name.into();
+        let reporter = self.handlers.error_reporter.clone();
+        self.on(command_name.clone(), Box::new(Reported::new(command_name, handler, reporter)));
+    }
+
+    /// Tell your bot what to do when it sees a command, with a handler that returns its reply
+    /// instead of sending it. The bot sends the reply, and errors sending it go to the bot's
+    /// `ErrorReporter` like errors from `on_try` handlers do.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, CommandReply};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_reply("roll", Box::new(|ctx: &CommandContext, args: &Vec<String>| {
+    ///     match args.first() {
+    ///         Some(sides) => Some(CommandReply::Text(format!("Rolling a d{}...", sides))),
+    ///         None => Some(CommandReply::Ephemeral("Usage: roll <sides>".to_owned()))
+    ///     }
+    /// }));
+    /// ```
+    pub fn on_reply<S: Into<String>>(&mut self, command_name: S, handler: Box<ReplyingCommandHandler + Send>) {
+        self.on_try(command_name, Box::new(Replying(handler)));
+    }
+
+    /// Tell your bot what to do with the errors that handlers added with `on_try` return, like
+    /// telling the user what went wrong.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, BotError};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_error(Box::new(|ctx: &mut CommandContext, command: &str, err: &BotError| {
+    ///     ctx.respond_in_channel(format!("Sorry, `{}` failed: {}", command, err));
+    /// }));
+    /// ```
+    pub fn on_error(&mut self, reporter: Box<ErrorReporter + Send>) {
+        *self.handlers.error_reporter.lock().unwrap() = Some(reporter);
+    }
+
+    /// Tell your bot what to do when it sees a command, with a handler that runs on a thread pool
+    /// rather than on the bot's event loop. Use this for commands that do slow things like calling
+    /// other APIs, so the bot can keep handling other commands in the meantime.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate futures;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::{SlackBot, AsyncSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_async("weather", Box::new(|sender: AsyncSender, args: Vec<String>| {
+    ///     futures::future::lazy(move || {
+    ///         // ...ask a weather service...
+    ///         sender.respond_in_channel("It's sunny!")
+    ///     })
+    /// }));
+    /// # }
+    /// ```
+    pub fn on_async<S: Into<String>>(&mut self, command_name: S, handler: Box<AsyncCommandHandler>) {
+        let command_name = command_name.into();
+        registry::add_name(&self.handlers.command_names, &command_name[..]);
+        if self.handlers.pool.is_none() {
+            self.handlers.pool = Some(CpuPool::new_num_cpus());
+        }
+        self.handlers.commands.lock().unwrap().remove(&command_name[..]);
+        self.handlers.async_commands.insert(command_name, handler);
+    }
+
+    /// Tell your bot what to do when it sees a command, along with a description of what the
+    /// command does.
+    ///
+    /// The description is shown by the built-in `help` command, and handlers can find it in
+    /// `CommandContext::command_description` to print their own usage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_with_help("say-hello", "Say hello to the world", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Hello, world!");
+    /// }));
+    /// ```
+    pub fn on_with_help<S: Into<String>>(&mut self, command_name: S, description: S, handler: Box<CommandHandler + Send>) {
+        let command_name = command_name.into();
+        self.config.descriptions.insert(command_name.clone(), description.into());
+        self.on(command_name, handler);
+    }
+
+    /// Tell your bot what to do when it sees a command that needs some arguments. When it's given
+    /// fewer than `min_args`, the bot replies with `usage` and the handler isn't called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_with_min_args("greet", 1, "Usage: `greet <name>`", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel(format!("Hello, {}!", args[0]));
+    /// }));
+    /// ```
+    pub fn on_with_min_args<S: Into<String>>(&mut self, command_name: S, min_args: usize, usage: S, handler: Box<CommandHandler + Send>) {
+        let command_name = command_name.into();
+        self.config.min_args.insert(command_name.clone(), (min_args, usage.into()));
+        self.on(command_name, handler);
+    }
+
+    /// Tell your bot what to do when it sees a command, but only in some channels. Channels can be
+    /// given by name (with or without the `#`) or by id.
+    ///
+    /// When the command is used in any other channel, it's ignored. Use
+    /// `explain_channel_restrictions` to have the bot say why instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_in_channels("restart", vec!["#admins".to_owned()], Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Restarting...");
+    /// }));
+    /// ```
+    pub fn on_in_channels<S: Into<String>>(&mut self, command_name: S, channels: Vec<String>, handler: Box<CommandHandler + Send>) {
+        let command_name = command_name.into();
+        self.config.allowed_channels.insert(command_name.clone(), channels);
+        self.on(command_name, handler);
+    }
+
+    /// Tell your bot what to do when it sees a command, but only from some users. Users can be
+    /// given by id or by name.
+    ///
+    /// When anyone else uses the command, the bot tells them they aren't allowed to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_authorized("deploy", vec!["U12345".to_owned()], Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Deploying...");
+    /// }));
+    /// ```
+    pub fn on_authorized<S: Into<String>>(&mut self, command_name: S, allowed_users: Vec<String>, handler: Box<CommandHandler + Send>) {
+        self.on_with_authorizer(command_name, Box::new(allowed_users), handler);
+    }
+
+    /// Tell your bot what to do when it sees a command, but only from users that the `Authorizer`
+    /// allows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate slack;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// use slack::User;
+    ///
+    /// let admins_only = |user: &User, command_name: &str| user.is_admin.unwrap_or(false);
+    /// my_bot.on_with_authorizer("deploy", Box::new(admins_only), Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Deploying...");
+    /// }));
+    /// # }
+    /// ```
+    pub fn on_with_authorizer<S: Into<String>>(&mut self, command_name: S, authorizer: Box<Authorizer>, handler: Box<CommandHandler + Send>) {
+        let command_name = command_name.into();
+        self.config.authorizers.insert(command_name.clone(), authorizer);
+        self.on(command_name, handler);
+    }
+
+    /// Tell your bot what to do when it sees a message that matches a pattern, anywhere in the
+    /// message. Patterns are only checked against messages that aren't commands, and every pattern
+    /// that matches has its handler called, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Sender, Regex};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let ticket = Regex::new(r"\b[A-Z]+-\d+\b").unwrap();
+    /// my_bot.on_match(ticket, Box::new(|sender: &mut Sender, captures: &Vec<String>| {
+    ///     sender.respond_in_thread(format!("https://jira.example.com/browse/{}", captures[0]));
+    /// }));
+    /// ```
+    pub fn on_match(&mut self, pattern: Regex, handler: Box<MatchHandler>) {
+        self.handlers.matchers.push((pattern, handler));
+    }
+
+    /// Tell your bot what to do with every message it sees, including commands. Edited and
+    /// deleted messages, messages from bots and the bot's own messages are skipped. Every handler
+    /// that's been added is called, in the order they were added, before the message is handled as
+    /// a command.
     ///
     /// # Examples
     ///
     /// ```
     /// # use slackbot::{SlackBot, Sender};
     /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
-    /// match my_bot.run() {
-    ///     Ok(()) => println!("Bot shut down successfully. Goodbye world!"),
-    ///     Err(err) => println!("Bot crashed. Error message: {}", err)
-    /// };
+    /// my_bot.on_any_message(Box::new(|sender: &mut Sender, text: &str| {
+    ///     if text.contains("deploy") {
+    ///         sender.react("eyes");
+    ///     }
+    /// }));
     /// ```
-    pub fn run(&mut self) -> Result<(), String> {
-        let mut client = RtmClient::new(&self.token[..]);
-        let mut handler = SlackBotEventHandler::new(&self.name[..], &mut self.handlers);
+    pub fn on_any_message(&mut self, handler: Box<MessageHandler>) {
+        self.handlers.messages.push(handler);
+    }
 
-        client.login_and_run(&mut handler)
+    /// Tell your bot what to do when someone adds or removes an emoji reaction on a message. Every
+    /// handler that's been added is called, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Sender, Reaction};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_reaction(Box::new(|sender: &mut Sender, reaction: &Reaction| {
+    ///     if reaction.emoji == "pushpin" && reaction.added {
+    ///         sender.respond_in_thread(format!("{} pinned this!", sender.user.name));
+    ///     }
+    /// }));
+    /// ```
+    pub fn on_reaction(&mut self, handler: Box<ReactionHandler>) {
+        self.handlers.reactions.push(handler);
+        self.process_event_type("reaction_added");
+        self.process_event_type("reaction_removed");
     }
-}
 
-/// A trait implemented by types that can handle commands.
-///
-/// # Examples
-///
-/// ```
-/// # use slackbot::{Sender, CommandHandler};
-/// struct SayHelloCommandHandler;
-///
-/// impl CommandHandler for SayHelloCommandHandler {
-///     fn handle(&mut self, sender: &mut Sender, args: &Vec<String>) {
-///         sender.respond_in_channel("Hello, world!");
-///     }
-/// }
-/// ```
-pub trait CommandHandler {
-    /// Handle the command.
-    fn handle(&mut self, sender: &mut Sender, args: &Vec<String>);
-}
+    /// Tell your bot what to do when someone edits a message, with what it said before and after.
+    /// Every handler that's been added is called, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Sender, MessageEdit};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_message_edited(Box::new(|sender: &mut Sender, edit: &MessageEdit| {
+    ///     println!("{} edited {:?} to say {:?}", sender.user.name, edit.previous_text, edit.new_text);
+    /// }));
+    /// ```
+    pub fn on_message_edited(&mut self, handler: Box<MessageEditHandler>) {
+        self.handlers.edits.push(handler);
+    }
+
+    /// Tell your bot what to do when someone clicks a button with this action id. Slack sends
+    /// clicks to your own web server rather than to the bot, so they have to be passed on with an
+    /// `ActionHandle`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Action, ChannelSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_action("approve", Box::new(|sender: &mut ChannelSender, action: &Action| {
+    ///     sender.send_message(format!("<@{}> approved it!", action.user_id));
+    /// }));
+    /// ```
+    pub fn on_action(&mut self, action_id: &str, handler: Box<ActionHandler>) {
+        self.handlers.actions.lock().unwrap().insert(action_id.to_owned(), handler);
+    }
+
+    /// Get a handle for passing on the payloads Slack sends to your web server when someone
+    /// clicks a button, so your bot's action handlers get them. Handlers reply using the bot's
+    /// web API token, so set that up first if it's different.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let actions = my_bot.action_handle();
+    /// ```
+    pub fn action_handle(&self) -> ActionHandle {
+        ActionHandle::new(self.handlers.actions.clone(), self.config.web_token(), self.handlers.outbox.clone())
+    }
+
+    /// Tell your bot what to do when someone joins a channel it's in. Every handler that's been
+    /// added is called, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Sender, MemberEvent};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_member_joined(Box::new(|sender: &mut Sender, event: &MemberEvent| {
+    ///     sender.respond_in_channel_raw(format!("Welcome, <@{}>!", event.user_id));
+    /// }));
+    /// ```
+    pub fn on_member_joined(&mut self, handler: Box<MemberEventHandler>) {
+        self.handlers.member_joined.push(handler);
+        self.process_event_type("member_joined_channel");
+        self.process_event_type("member_left_channel");
+    }
+
+    /// Tell your bot what to do when someone leaves a channel it's in. Every handler that's been
+    /// added is called, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Sender, MemberEvent};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_member_left(Box::new(|sender: &mut Sender, event: &MemberEvent| {
+    ///     sender.respond_in_channel(format!("Bye, {}!", sender.user.name));
+    /// }));
+    /// ```
+    pub fn on_member_left(&mut self, handler: Box<MemberEventHandler>) {
+        self.handlers.member_left.push(handler);
+        self.process_event_type("member_joined_channel");
+        self.process_event_type("member_left_channel");
+    }
+
+    /// Tell your bot what to do when it's added to a channel, like posting a greeting. With
+    /// `auto_join_on_invite` on, this is called once the bot's joined. The event's user is the
+    /// bot itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Sender, MemberEvent};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_joined_channel(Box::new(|sender: &mut Sender, event: &MemberEvent| {
+    ///     sender.respond_in_channel("Hi! Say `!bot help` to see what I can do.");
+    /// }));
+    /// ```
+    pub fn on_joined_channel(&mut self, handler: Box<MemberEventHandler>) {
+        self.handlers.joined_channel.push(handler);
+        self.process_event_type("member_joined_channel");
+        self.process_event_type("member_left_channel");
+    }
+
+    /// Have your bot join channels it's invited to with `conversations.join`, so it gets
+    /// everything said there. Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.auto_join_on_invite(true);
+    /// ```
+    pub fn auto_join_on_invite(&mut self, enabled: bool) {
+        self.config.auto_join_on_invite = enabled;
+        if enabled {
+            self.process_event_type("member_joined_channel");
+        }
+    }
+
+    /// Run something each time your bot connects to Slack, like announcing that it's online.
+    /// Every hook that's been added is called, in the order they were added, after the bot knows
+    /// who it is and who's in the team.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate slack;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// use slack::RtmClient;
+    ///
+    /// my_bot.on_startup(Box::new(|cli: &mut RtmClient| {
+    ///     let _ = cli.send_message("C12345", "I'm online!");
+    /// }));
+    /// # }
+    /// ```
+    pub fn on_startup(&mut self, hook: Box<FnMut(&mut RtmClient)>) {
+        self.handlers.startup.push(hook);
+    }
+
+    /// Run something each time your bot's connection to Slack closes, whether it's stopping or
+    /// about to reconnect. Every hook that's been added is called, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate slack;
+    /// # extern crate slackbot;
+    /// # fn main() {
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// use slack::RtmClient;
+    ///
+    /// my_bot.on_shutdown(Box::new(|cli: &mut RtmClient| {
+    ///     println!("Disconnected from Slack");
+    /// }));
+    /// # }
+    /// ```
+    pub fn on_shutdown(&mut self, hook: Box<FnMut(&mut RtmClient)>) {
+        self.handlers.shutdown.push(hook);
+    }
+
+    /// Have your bot run a task on a schedule while it's running, given as a five field cron
+    /// expression in UTC (e.g. `0 9 * * 1-5` for 9am every weekday). The task posts to the given
+    /// channel, which can be a name with a leading `#` or an id.
+    ///
+    /// Tasks run one at a time on a thread of their own, so they won't hold up commands. A task
+    /// that panics is logged, and the rest keep running on schedule.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, ChannelSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.schedule("0 9 * * 1-5", "#team", Box::new(|sender: &mut ChannelSender| {
+    ///     sender.send_message("Standup time!");
+    /// })).unwrap();
+    /// ```
+    pub fn schedule(&mut self, cron_expr: &str, channel: &str, task: Box<ScheduledTask>) -> Result<(), BotError> {
+        self.jobs.push(Job {
+            schedule: try!(CronSchedule::parse(cron_expr)),
+            channel: channel.to_owned(),
+            task: task
+        });
+        Ok(())
+    }
+
+    /// Have your bot reply when a command is used in a channel it isn't allowed in, rather than
+    /// ignoring it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.explain_channel_restrictions(true);
+    /// ```
+    pub fn explain_channel_restrictions(&mut self, enabled: bool) {
+        self.config.explain_channel_restrictions = enabled;
+    }
+
+    /// Tell your bot what to do when it sees a command it doesn't know. The handler gets the name
+    /// of the command that was attempted as its first argument, followed by the command's
+    /// arguments.
+    ///
+    /// Unless you've set this, your bot replies with a hint to try `help`. Bots with an empty
+    /// prefix, where every message is a command attempt, stay quiet instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_unknown(Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel(format!("I don't know how to {}", args[0]));
+    /// }));
+    /// ```
+    pub fn on_unknown(&mut self, handler: Box<CommandHandler + Send>) {
+        self.handlers.unknown = Some(Box::new(Stateless(handler)));
+    }
+
+    /// Stop your bot from handling a command, forgetting its description, cooldown and any channel,
+    /// user or alias restrictions. If it was a regular handler, it's given back.
+    ///
+    /// Like adding commands, this is done before running the bot or between runs. Use a
+    /// `CommandRegistry` to remove commands while it's running.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("beta", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Not quite ready yet!");
+    /// }));
+    /// let beta = my_bot.off("beta");
+    /// assert!(beta.is_some());
+    /// ```
+    pub fn off<S: Into<String>>(&mut self, command_name: S) -> Option<Box<StatefulCommandHandler<T> + Send>> {
+        let command_name = command_name.into();
+        self.handlers.command_names.lock().unwrap().retain(|name| *name != command_name);
+        self.config.descriptions.remove(&command_name);
+        self.config.allowed_channels.remove(&command_name);
+        self.config.authorizers.remove(&command_name);
+        self.config.cooldowns.remove(&command_name);
+        self.config.min_args.remove(&command_name);
+        self.config.aliases.retain(|_, target| *target != command_name);
+
+        self.handlers.async_commands.remove(&command_name);
+        self.handlers.commands.lock().unwrap().remove(&command_name[..])
+    }
+
+    /// Stop your bot from handling any commands, as if `off` was used on each of them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.clear_commands();
+    /// ```
+    pub fn clear_commands(&mut self) {
+        let command_names = self.handlers.command_names.lock().unwrap().clone();
+        for command_name in command_names {
+            self.off(command_name);
+        }
+    }
+
+    /// Get a handle that can add and remove commands from anywhere, even while your bot is
+    /// running. Commands added this way can use everything `on` and `on_stateful` can, except for
+    /// descriptions and restrictions, which are set up before the bot runs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::thread;
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let registry = my_bot.command_registry();
+    /// thread::spawn(move || {
+    ///     registry.on("status", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///         ctx.respond_in_channel("All systems go");
+    ///     }));
+    /// }).join().unwrap();
+    /// ```
+    pub fn command_registry(&self) -> CommandRegistry<T> {
+        self.handlers.registry()
+    }
+
+    /// Run some middleware before every command. Middleware runs in the order it was added, and
+    /// any of it can stop a command from being handled or change which command is handled. See
+    /// `Middleware` for how changes are picked up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.use_middleware(Box::new(|ctx: &CommandContext| {
+    ///     println!("{} ran {}", ctx.user.name, ctx.command);
+    ///     true
+    /// }));
+    /// ```
+    pub fn use_middleware(&mut self, middleware: Box<Middleware>) {
+        self.handlers.middleware.push(middleware);
+    }
+
+    /// Keep track of how long each command takes and whether it succeeds. Without metrics,
+    /// commands aren't timed at all.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.with_metrics(Box::new(|command: &str, duration: Duration, success: bool| {
+    ///     println!("{} took {:?}", command, duration);
+    /// }));
+    /// ```
+    pub fn with_metrics(&mut self, metrics: Box<Metrics>) {
+        self.handlers.metrics = Some(Arc::from(metrics));
+    }
+
+    /// Listen for what your bot does while it's running, like connecting and handling commands.
+    /// Each call gets its own stream of every event from then on, which keeps going through
+    /// reconnects.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::thread;
+    /// # use slackbot::{SlackBot, BotEvent};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let events = my_bot.events();
+    /// thread::spawn(move || {
+    ///     for event in events {
+    ///         if let BotEvent::CommandCompleted { command, duration, .. } = event {
+    ///             println!("{} took {:?}", command, duration);
+    ///         }
+    ///     }
+    /// });
+    /// my_bot.run();
+    /// ```
+    pub fn events(&self) -> Receiver<BotEvent> {
+        self.handlers.subscribers.subscribe()
+    }
+
+    /// Limit how many commands each user can run in a minute. Commands over the limit aren't
+    /// handled, and the bot asks the user to slow down instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.rate_limit(10);
+    /// ```
+    pub fn rate_limit(&mut self, max_per_minute: u32) {
+        self.handlers.rate_limiter = Some(RateLimiter::new(max_per_minute));
+    }
+
+    /// Only let a command run once every so many seconds, across everyone that uses it. Anyone
+    /// that tries it sooner is told how long to wait, and the handler isn't called.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Deploying...");
+    /// }));
+    /// my_bot.cooldown("deploy", 300);
+    /// ```
+    pub fn cooldown<S: Into<String>>(&mut self, command_name: S, seconds: u64) {
+        self.config.cooldowns.insert(command_name.into(), seconds);
+    }
+
+    /// Declare which of a command's options take a value from the argument after them, so
+    /// `--env prod` gives `env` the value `prod` in `CommandContext::parsed_args`. Other options
+    /// are flags unless they're given as `--name=value`. Names don't have their leading `--`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.options_taking_values("deploy", &["env", "tag"]);
+    /// ```
+    pub fn options_taking_values<S: Into<String>>(&mut self, command_name: S, options: &[&str]) {
+        let options = options.iter().map(|option| option.trim_left_matches('-').to_owned()).collect();
+        self.config.value_options.insert(command_name.into(), options);
+    }
+
+    /// Warn when a command's handler runs for longer than `timeout`, logging it and sending a
+    /// `BotEvent::CommandOverran` to anything watching the bot's events.
+    ///
+    /// Handlers can't be stopped partway, so one that overruns keeps going. On worker threads
+    /// (see `worker_threads`), the command is abandoned: it's reported as having failed, its
+    /// thread is replaced so the other commands aren't held up, and the bot doesn't wait for it
+    /// when it stops. It stays busy until its handler finishes, if it ever does, and then it's put
+    /// back. Commands on the event loop hold up the bot until they finish.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.handler_timeout(Duration::from_secs(30));
+    /// ```
+    pub fn handler_timeout(&mut self, timeout: Duration) {
+        self.config.handler_timeout = Some(timeout);
+    }
+
+    /// Leave at least `millis` milliseconds between the bot's messages to each channel, rather than
+    /// a second. Messages wait their turn, so they always go out in the order they were sent in;
+    /// with no time between them, they're only kept in order.
+    ///
+    /// Slack rate limits bots that send more than about a message a second to a channel, so going
+    /// faster than that for long can get messages dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.message_interval(2000);
+    /// ```
+    pub fn message_interval(&mut self, millis: u64) {
+        self.handlers.outbox.set_interval(Duration::from_millis(millis));
+    }
+
+    /// Have your bot show up as active or away when it connects, rather than however Slack
+    /// decides. `Presence::Active` hands the decision back to Slack, which shows bots as active
+    /// while they're connected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Presence};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.set_presence(Presence::Away);
+    /// ```
+    pub fn set_presence(&mut self, presence: Presence) {
+        self.config.presence = Some(presence);
+    }
+
+    /// Check that the bot's tokens start like Slack tokens (`xoxb-` or `xoxp-`, say) before it
+    /// logs in, which is on by default. Turn this off for setups with tokens that don't, like a
+    /// proxy in front of Slack. Tokens that are empty or are webhook URLs are never allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, BotError};
+    /// let mut my_bot = SlackBot::new("bot", "https://hooks.slack.com/services/T0/B0/XXXX");
+    /// match my_bot.run() {
+    ///     Err(BotError::InvalidToken(_)) => {},
+    ///     _ => panic!("webhook URLs aren't tokens")
+    /// }
+    ///
+    /// let mut proxied_bot = SlackBot::new("bot", "my-proxy-token");
+    /// proxied_bot.check_token_format(false);
+    /// ```
+    pub fn check_token_format(&mut self, enabled: bool) {
+        self.config.check_token_format = enabled;
+    }
+
+    /// Use a different token for calls to Slack's web API, like sending attachments, uploading
+    /// files and reacting to messages. Without one, the bot's token is used for everything.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.with_web_token("YOUR_WEB_API_TOKEN");
+    /// ```
+    pub fn with_web_token(&mut self, token: &str) {
+        self.config.web_token = Some(token.to_owned());
+    }
+
+    /// Have your bot ignore commands with more than this many arguments, so handlers don't have to
+    /// guard against huge inputs themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.max_args(10);
+    /// ```
+    pub fn max_args(&mut self, n: usize) {
+        self.config.max_args = Some(n);
+    }
+
+    /// Have your bot ignore commands in messages longer than this many bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.max_message_len(2000);
+    /// ```
+    pub fn max_message_len(&mut self, bytes: usize) {
+        self.config.max_message_len = Some(bytes);
+    }
+
+    /// Split the bot's messages into several when they're longer than this many bytes, on line
+    /// breaks where possible. By default, messages are split at 4000 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.max_response_len(1000);
+    /// ```
+    pub fn max_response_len(&mut self, bytes: usize) {
+        self.config.max_response_len = bytes;
+    }
+
+    /// Have your bot reply when a command is over its `max_args` or `max_message_len` limits,
+    /// rather than ignoring it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.max_args(10);
+    /// my_bot.explain_input_limits(true);
+    /// ```
+    pub fn explain_input_limits(&mut self, enabled: bool) {
+        self.config.explain_input_limits = enabled;
+    }
+
+    /// Let a command be run by another name.
+    ///
+    /// The command doesn't have to exist yet, but it does by the time the bot runs. Otherwise,
+    /// `run` returns an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("weather", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Sunny!");
+    /// }));
+    /// my_bot.alias("wx", "weather");
+    /// my_bot.alias("forecast", "weather");
+    /// ```
+    pub fn alias<S: Into<String>>(&mut self, alias: S, target: S) {
+        self.config.aliases.insert(alias.into(), target.into());
+    }
+
+    /// Let people run commands by @-mentioning your bot (e.g. `@bot echo Hello world!`), as well as
+    /// with its prefix.
+    ///
+    /// This looks for mentions in plain messages. Slack's `app_mention` events are always treated
+    /// as commands, so bots that process them don't need this. A mention that comes as both is only
+    /// handled once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.respond_to_mentions(true);
+    /// ```
+    pub fn respond_to_mentions(&mut self, enabled: bool) {
+        self.config.respond_to_mentions = enabled;
+    }
+
+    /// Ignore commands in replies to threads, so your bot only answers messages sent straight to
+    /// a channel. Messages that start a thread aren't replies, so they still count.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.ignore_thread_replies(true);
+    /// ```
+    pub fn ignore_thread_replies(&mut self, enabled: bool) {
+        if enabled {
+            self.config.threads = Threads::TopLevel;
+        } else if self.config.threads == Threads::TopLevel {
+            self.config.threads = Threads::All;
+        }
+    }
+
+    /// Only look for commands in replies to threads, and ignore them everywhere else. Turning
+    /// this on turns `ignore_thread_replies` off, and the other way around.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.only_thread_replies(true);
+    /// ```
+    pub fn only_thread_replies(&mut self, enabled: bool) {
+        if enabled {
+            self.config.threads = Threads::RepliesOnly;
+        } else if self.config.threads == Threads::RepliesOnly {
+            self.config.threads = Threads::All;
+        }
+    }
+
+    /// Change the command your bot runs when it sees its prefix on its own (e.g. just `!bot`). By
+    /// default, this is `help`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("menu", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Today's special is soup");
+    /// }));
+    /// my_bot.default_command("menu");
+    /// ```
+    pub fn default_command(&mut self, command_name: &str) {
+        self.config.default_command = Some(command_name.to_owned());
+    }
+
+    /// Give your bot a command that lists every command it has, along with their descriptions
+    /// and restrictions like channels, authorizers, cooldowns and aliases. Only `admins` can use
+    /// it, given by user id or name. The command is called `_commands` unless it's renamed with
+    /// `admin_command_name`, and doesn't show up in `help`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on_with_help("deploy", "Deploys the app", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Deploying...");
+    /// }));
+    /// my_bot.cooldown("deploy", 60);
+    /// my_bot.enable_admin_command(vec!["U123".to_owned()]);
+    ///
+    /// let sent = my_bot.run_with_events(&[
+    ///     r#"{"type": "message", "channel": "C123", "user": "U123", "ts": "1.0", "text": "!bot _commands"}"#,
+    ///     r#"{"type": "message", "channel": "C123", "user": "U456", "ts": "2.0", "text": "!bot _commands"}"#
+    /// ]).unwrap();
+    /// assert_eq!(sent[0].text, "All commands (1):\n`deploy` — Deploys the app (60s cooldown)");
+    /// assert_eq!(sent[1].text, "Sorry, you aren't allowed to use `_commands`");
+    /// ```
+    pub fn enable_admin_command(&mut self, admins: Vec<String>) {
+        self.config.admins = Some(admins);
+    }
+
+    /// Change the name of the command added by `enable_admin_command`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.enable_admin_command(vec!["U123".to_owned()]);
+    /// my_bot.admin_command_name("commands-admin");
+    /// ```
+    pub fn admin_command_name<S: Into<String>>(&mut self, command_name: S) {
+        self.config.admin_command = command_name.into();
+    }
+
+    /// Have your bot ignore its prefix on its own, rather than running a default command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.disable_default_command();
+    /// ```
+    pub fn disable_default_command(&mut self) {
+        self.config.default_command = None;
+    }
+
+    /// Let your bot handle commands from messages with these subtypes.
+    ///
+    /// By default, edited messages (`message_changed`), deleted messages (`message_deleted`) and
+    /// messages from bots (`bot_message`) are ignored so that your bot doesn't end up talking to
+    /// itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.handle_subtypes(vec!["bot_message".to_owned()]);
+    /// ```
+    pub fn handle_subtypes(&mut self, subtypes: Vec<String>) {
+        self.config.handled_subtypes = subtypes;
+    }
+
+    /// Only process events from Slack with these types, besides the ones the bot needs itself, and
+    /// skip everything else after reading just its type. By default only `message` events are
+    /// processed, since big teams can get floods of events like `presence_change` and
+    /// `user_typing` that most bots never look at.
+    ///
+    /// The bot always processes `user_change` and `team_join` events, to keep its list of users up
+    /// to date, `app_mention` events when it `respond_to_mentions`, and `presence_change` events
+    /// once a command has looked up someone's presence, to keep it up to date. Reaction and member
+    /// handlers, and `auto_join_on_invite`, add the events they need whenever they're set up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.process_event_types(vec!["message".to_owned()]);
+    /// ```
+    pub fn process_event_types(&mut self, types: Vec<String>) {
+        self.config.event_types = types.into_iter().collect();
+    }
+
+    /// Change the messages your bot sends by itself, like its `help` list, its reply to unknown
+    /// commands and its rate limit notice, to word them differently or translate them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Messages, RecordedMessage};
+    /// let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.disable_suggestions();
+    /// my_bot.with_messages(Messages {
+    ///     unknown_command_help: "Commande inconnue `{command}`, essayez `{prefix} help`".to_owned(),
+    ///     ..Messages::default()
+    /// });
+    ///
+    /// let sent = my_bot.run_with_events(&[
+    ///     r#"{"type": "message", "channel": "C123", "user": "U123", "ts": "1.0", "text": "!bot deploy"}"#
+    /// ]).unwrap();
+    /// assert_eq!(sent[0].text, "Commande inconnue `deploy`, essayez `!bot help`");
+    /// ```
+    pub fn with_messages(&mut self, messages: Messages) {
+        self.config.messages = messages;
+    }
+
+    /// Stop your bot from answering `help` with a list of its commands.
+    ///
+    /// Unless you've told your bot what to do on `help` yourself, it responds with the names of
+    /// every command it knows, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.disable_default_help();
+    /// ```
+    pub fn disable_default_help(&mut self) {
+        self.default_help = false;
+    }
+
+    /// Stop your bot from suggesting a command with a similar name when someone tries a command
+    /// that doesn't exist.
+    ///
+    /// Unless you've told your bot what to do with unknown commands yourself, it replies to
+    /// typos like `ehco` with "did you mean `echo`?".
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.disable_suggestions();
+    /// ```
+    pub fn disable_suggestions(&mut self) {
+        self.suggest_commands = false;
+    }
+
+    /// Have your bot reconnect when its connection to Slack drops, rather than stopping.
+    ///
+    /// The bot waits a little before each attempt, waiting twice as long each time up to about a
+    /// minute. Once it manages to connect again, the wait goes back to the start. It gives up after
+    /// `max_retries` failed attempts in a row, or never if that's `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.with_auto_reconnect(Some(10));
+    /// ```
+    pub fn with_auto_reconnect(&mut self, max_retries: Option<usize>) {
+        self.auto_reconnect = true;
+        self.max_retries = max_retries;
+    }
+
+    /// Change how many recent messages your bot remembers, so that a message Slack sends more than
+    /// once is only handled the first time. It remembers the last 100 by default, even across
+    /// reconnects, and 0 turns this off.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.dedupe_window(500);
+    /// ```
+    pub fn dedupe_window(&mut self, n: usize) {
+        self.recent_messages.set_capacity(n);
+    }
+
+    /// Get a handle that can stop your bot while it's running. To run the bot on a thread of its
+    /// own, see `run_with_handle`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::SlackBot;
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let handle = my_bot.shutdown_handle();
+    /// ```
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle::new(self.shutdown.clone())
+    }
+
+    /// Tell your bot to start pulling its weight!
+    ///
+    /// This blocks until the connection to Slack closes (unless the bot is set to reconnect), or
+    /// the bot is stopped with a `ShutdownHandle`, and then until the commands still running on
+    /// worker threads or as async commands have finished. A token that can't be right is caught before
+    /// logging in (see `check_token_format`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, Sender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// match my_bot.run() {
+    ///     Ok(()) => println!("Bot shut down successfully. Goodbye world!"),
+    ///     Err(err) => println!("Bot crashed. Error message: {}", err)
+    /// };
+    /// ```
+    pub fn run(&mut self) -> Result<(), BotError> {
+        try!(self.config.check_tokens());
+        try!(self.check_aliases());
+
+        if let Some(ref persistence) = self.handlers.persistence {
+            if let Some(state) = try!(persistence.load()) {
+                self.state = state;
+            }
+        }
+
+        let defaults = self.add_default_handlers();
+        let scheduler = if self.jobs.is_empty() {
+            None
+        } else {
+            Some(Scheduler::start(self.config.web_token().to_owned(), self.handlers.outbox.clone(), self.jobs.drain(..).collect()))
+        };
+
+        let result = self.run_with_reconnects();
+        // Commands that are still running could change the state, so it isn't saved until
+        // they're done
+        self.handlers.in_flight.wait();
+        self.shutdown.lock().unwrap().reset();
+
+        if let Some(scheduler) = scheduler {
+            self.jobs = scheduler.stop();
+        }
+
+        self.remove_default_handlers(defaults);
+
+        let saved = match self.handlers.persistence {
+            Some(ref mut persistence) => persistence.save(&self.state),
+            None => Ok(())
+        };
+        result.and(saved)
+    }
+
+    /// Handle events as if Slack had sent them, without connecting to Slack, and get back what
+    /// the bot would have sent. This is for testing a whole bot, from parsing commands to calling
+    /// handlers, somewhere that can't reach Slack.
+    ///
+    /// Events are handled in order, and so are commands, even with `worker_threads`. The bot
+    /// doesn't ask Slack about anything, so users it hasn't seen a `team_join` or `user_change`
+    /// event for are only known by their id, and the bot itself is `U0BOT`, so it's mentioned as
+    /// `<@U0BOT>`. Async commands and scheduled jobs don't run, since they talk to Slack
+    /// themselves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, RecordedMessage};
+    /// let mut echo_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// echo_bot.on("echo", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel(args.join(" "));
+    /// }));
+    ///
+    /// let sent = echo_bot.run_with_events(&[
+    ///     r#"{"type": "message", "channel": "C123", "user": "U123", "ts": "1.0", "text": "!bot echo hi"}"#,
+    ///     r#"{"type": "message", "channel": "C123", "user": "U123", "ts": "2.0", "text": "not for the bot"}"#
+    /// ]).unwrap();
+    /// assert_eq!(sent, vec![RecordedMessage { channel: "C123".to_owned(), text: "hi".to_owned() }]);
+    /// ```
+    pub fn run_with_events(&mut self, events: &[&str]) -> Result<Vec<RecordedMessage>, BotError> {
+        try!(self.check_aliases());
+        let defaults = self.add_default_handlers();
+
+        let recording: Recording = Arc::new(Mutex::new(Vec::new()));
+        {
+            let mut client = RtmClient::new(&self.config.token[..]);
+            let mut handler = SlackBotEventHandler::new(&self.config,
+                                                        &mut self.handlers,
+                                                        &mut self.state,
+                                                        &mut self.recent_messages,
+                                                        self.shutdown.clone());
+            handler.record(recording.clone());
+            for event in events {
+                handler.on_receive(&mut client, event);
+            }
+        }
+
+        self.remove_default_handlers(defaults);
+        let sent = recording.lock().unwrap().drain(..).collect();
+        Ok(sent)
+    }
+
+    /// Process events of this type too, for handlers that need them.
+    fn process_event_type(&mut self, ty: &str) {
+        self.config.handler_event_types.insert(ty.to_owned());
+    }
+
+    /// Make sure every alias is for a command the bot will have.
+    fn check_aliases(&self) -> Result<(), BotError> {
+        let add_default_help = self.default_help && !self.handlers.has_command("help");
+        for (alias, target) in self.config.aliases.iter() {
+            if !self.handlers.has_command(target) && !(add_default_help && target == "help") {
+                return Err(BotError::Config(format!("Alias `{}` is for unknown command `{}`", alias, target)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the `help` command and unknown command handler, if the bot doesn't have its own.
+    fn add_default_handlers(&mut self) -> DefaultHandlers {
+        let add_default_help = self.default_help && !self.handlers.has_command("help");
+        if add_default_help {
+            let help = HelpCommandHandler::new(self.handlers.command_names.clone(), &self.config.descriptions,
+                                              &self.config.messages.help_header[..]);
+            self.handlers.commands.lock().unwrap().insert("help".to_owned(), Box::new(Stateless(Box::new(help))));
+        }
+
+        let add_default_unknown = self.handlers.unknown.is_none() && !self.config.prefix.is_empty();
+        if add_default_unknown {
+            let mut suggestions = Vec::new();
+            if self.suggest_commands {
+                suggestions.extend(self.handlers.command_names.lock().unwrap().iter().cloned());
+                suggestions.extend(self.config.aliases.keys().cloned());
+            }
+            let unknown = UnknownCommandHandler::new(&self.config.prefix[..], self.handlers.has_command("help"), suggestions,
+                                                     &self.config.messages);
+            self.handlers.unknown = Some(Box::new(Stateless(Box::new(unknown))));
+        }
+
+        // The admin command isn't in the list of commands, so it doesn't show up in `help`
+        let add_admin = self.config.admins.is_some() && !self.handlers.has_command(&self.config.admin_command[..]);
+        if add_admin {
+            let admin = AdminCommandHandler::new(self.handlers.command_names.clone(), &self.config);
+            let admins = self.config.admins.clone().unwrap_or_default();
+            self.config.authorizers.insert(self.config.admin_command.clone(), Box::new(admins));
+            self.handlers.commands.lock().unwrap().insert(self.config.admin_command.clone(), Box::new(Stateless(Box::new(admin))));
+        }
+
+        DefaultHandlers {
+            help: add_default_help,
+            unknown: add_default_unknown,
+            admin: add_admin
+        }
+    }
+
+    /// Take away the default handlers again, so the bot's the same as before it ran.
+    fn remove_default_handlers(&mut self, defaults: DefaultHandlers) {
+        if defaults.help {
+            self.handlers.commands.lock().unwrap().remove("help");
+        }
+        if defaults.unknown {
+            self.handlers.unknown = None;
+        }
+        if defaults.admin {
+            self.handlers.commands.lock().unwrap().remove(&self.config.admin_command[..]);
+            self.config.authorizers.remove(&self.config.admin_command);
+        }
+    }
+
+    fn run_with_reconnects(&mut self) -> Result<(), BotError> {
+        let mut retries = 0;
+        let mut backoff = Duration::from_secs(INITIAL_BACKOFF_SECS);
+        loop {
+            let (result, connected) = self.run_once();
+            if !self.auto_reconnect || self.shutdown.lock().unwrap().is_stopping() {
+                return result;
+            }
+
+            if connected {
+                retries = 0;
+                backoff = Duration::from_secs(INITIAL_BACKOFF_SECS);
+            }
+            if self.max_retries.map_or(false, |max_retries| retries >= max_retries) {
+                return result;
+            }
+
+            match result {
+                Ok(()) => warn!("Connection to Slack closed, reconnecting in {}s", backoff.as_secs()),
+                Err(err) => warn!("Connection to Slack failed ({}), reconnecting in {}s", err, backoff.as_secs())
+            }
+            thread::sleep(backoff);
+            retries += 1;
+            backoff = cmp::min(backoff * 2, Duration::from_secs(MAX_BACKOFF_SECS));
+        }
+    }
+
+    /// Connect to Slack and handle events until the connection closes. Also returns whether the
+    /// bot managed to connect at all.
+    fn run_once(&mut self) -> (Result<(), BotError>, bool) {
+        let mut client = RtmClient::new(&self.config.token[..]);
+        let mut handler = SlackBotEventHandler::new(&self.config,
+                                                    &mut self.handlers,
+                                                    &mut self.state,
+                                                    &mut self.recent_messages,
+                                                    self.shutdown.clone());
+
+        let result = client.login_and_run(&mut handler).map_err(|err| {
+            // The slack crate only gives us strings, but login failures are always prefixed
+            if err.starts_with("Error at Login") {
+                BotError::Login(err)
+            } else {
+                BotError::Slack(err)
+            }
+        });
+        (result, handler.has_connected())
+    }
+}
+
+impl<T: 'static> SlackBot<T> {
+    /// Handle commands on a pool of `threads` threads rather than on the bot's event loop, so a
+    /// slow command doesn't hold up the others. Commands that use the bot's state still run on
+    /// the event loop, where the state is.
+    ///
+    /// Each handler still handles one command at a time, so when a command comes in while its
+    /// handler is busy with the last one, the bot says so instead. A handler that panics on a
+    /// worker is logged and its command fails, but the handler is kept for the next command. With
+    /// no threads, every command runs on the event loop again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.worker_threads(4);
+    /// my_bot.on("report", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     // ...something slow...
+    ///     ctx.respond_in_channel("Here's your report");
+    /// }));
+    /// ```
+    pub fn worker_threads(&mut self, threads: usize) {
+        self.handlers.workers = if threads == 0 {
+            None
+        } else {
+            Some(Workers::new(threads))
+        };
+    }
+
+    /// Run a bot on a thread of its own, getting back a handle that stops it and the thread, which
+    /// finishes with what `run` returned once the bot has stopped. For bots that are part of a
+    /// bigger service, which needs to stop or restart them.
+    ///
+    /// The bot is made by `make_bot` on its own thread, since bots can have handlers that have to
+    /// stay on the thread they were made on.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// let (handle, bot) = SlackBot::run_with_handle(|| {
+    ///     let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    ///     my_bot.on("ping", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///         ctx.respond_in_channel("pong");
+    ///     }));
+    ///     my_bot
+    /// });
+    ///
+    /// // ...later, when the service is shutting down...
+    /// handle.stop();
+    /// bot.join().unwrap().unwrap();
+    /// ```
+    pub fn run_with_handle<F>(make_bot: F) -> (ShutdownHandle, JoinHandle<Result<(), BotError>>)
+        where F: FnOnce() -> Self + Send + 'static {
+
+        let (handle_tx, handle_rx) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut bot = make_bot();
+            let _ = handle_tx.send(bot.shutdown_handle());
+            bot.run()
+        });
+        // If making the bot panicked, there's nothing to stop, and the thread has the panic
+        let handle = handle_rx.recv().unwrap_or_else(|_| ShutdownHandle::new(ShutdownState::new()));
+        (handle, thread)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> SlackBot<T> {
+    /// Keep the bot's state in a store, so it isn't lost when the bot restarts. The state is
+    /// loaded from the store under `key` when the bot starts running, if anything was saved
+    /// there, and saved when the bot stops. With `checkpoint_secs`, it's also saved every so often
+    /// while the bot runs, when a command comes in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use std::env;
+    /// use slackbot::{SlackBot, JsonFileStore};
+    ///
+    /// let scores: HashMap<String, u32> = HashMap::new();
+    /// let mut my_bot = SlackBot::new_with_state("bot", "YOUR_API_TOKEN", scores);
+    /// let store = JsonFileStore::new(env::temp_dir().join("scores.json"));
+    /// my_bot.persist_state(Box::new(store), "scores", Some(60));
+    /// ```
+    pub fn persist_state(&mut self, store: Box<StateStore>, key: &str, checkpoint_secs: Option<u64>) {
+        let checkpoint = checkpoint_secs.map(Duration::from_secs);
+        self.handlers.persistence = Some(Persistence::new(store, key.to_owned(), checkpoint));
+    }
+}
+
+/// A trait implemented by types that can handle commands.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{CommandContext, CommandHandler};
+/// struct SayHelloCommandHandler;
+///
+/// impl CommandHandler for SayHelloCommandHandler {
+///     fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) {
+///         ctx.respond_in_channel("Hello, world!");
+///     }
+/// }
+/// ```
+pub trait CommandHandler {
+    /// Handle the command.
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>);
+}
+
+impl<F> CommandHandler for F where F: FnMut(&mut CommandContext, &Vec<String>) {
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) {
+        self(ctx, args);
+    }
+}
+
+/// A trait implemented by types that can handle commands using the bot's state.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{CommandContext, StatefulCommandHandler};
+/// struct CountCommandHandler;
+///
+/// impl StatefulCommandHandler<u32> for CountCommandHandler {
+///     fn handle(&mut self, count: &mut u32, ctx: &mut CommandContext, args: &Vec<String>) {
+///         *count += 1;
+///         ctx.respond_in_channel(format!("I've counted to {}", count));
+///     }
+/// }
+/// ```
+pub trait StatefulCommandHandler<T> {
+    /// Handle the command.
+    fn handle(&mut self, state: &mut T, ctx: &mut CommandContext, args: &Vec<String>);
+
+    /// The handler as a plain `CommandHandler`, if it doesn't use the bot's state. Only handlers
+    /// that don't can run on the bot's worker threads (see `SlackBot::worker_threads`).
+    fn without_state(&mut self) -> Option<&mut CommandHandler> {
+        None
+    }
+}
+
+impl<T, F> StatefulCommandHandler<T> for F where F: FnMut(&mut T, &mut CommandContext, &Vec<String>) {
+    fn handle(&mut self, state: &mut T, ctx: &mut CommandContext, args: &Vec<String>) {
+        self(state, ctx, args);
+    }
+}
+
+/// A trait implemented by types that can handle commands, but might fail. See `SlackBot::on_try`.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{CommandContext, TryCommandHandler, BotError};
+/// struct EchoCommandHandler;
+///
+/// impl TryCommandHandler for EchoCommandHandler {
+///     fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) -> Result<(), BotError> {
+///         ctx.respond_in_channel(args.join(" ")).wait().map(|_| ())
+///     }
+/// }
+/// ```
+pub trait TryCommandHandler {
+    /// Handle the command.
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) -> Result<(), BotError>;
+}
+
+impl<F> TryCommandHandler for F where F: FnMut(&mut CommandContext, &Vec<String>) -> Result<(), BotError> {
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) -> Result<(), BotError> {
+        self(ctx, args)
+    }
+}
+
+/// A trait implemented by types that handle commands by returning what to say back, or `None` to
+/// say nothing. See `SlackBot::on_reply`. Handlers like these can be tested by checking what they
+/// return.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{CommandContext, ReplyingCommandHandler, CommandReply};
+/// struct EchoCommandHandler;
+///
+/// impl ReplyingCommandHandler for EchoCommandHandler {
+///     fn handle(&mut self, ctx: &CommandContext, args: &Vec<String>) -> Option<CommandReply> {
+///         if args.is_empty() {
+///             None
+///         } else {
+///             Some(CommandReply::Text(args.join(" ")))
+///         }
+///     }
+/// }
+///
+/// let ctx = CommandContext::test();
+/// match EchoCommandHandler.handle(&ctx, &vec!["hi".to_owned()]) {
+///     Some(CommandReply::Text(message)) => assert_eq!(message, "hi"),
+///     reply => panic!("Unexpected reply: {:?}", reply)
+/// }
+/// assert!(EchoCommandHandler.handle(&ctx, &vec![]).is_none());
+/// ```
+pub trait ReplyingCommandHandler {
+    /// Handle the command, returning the reply to send.
+    fn handle(&mut self, ctx: &CommandContext, args: &Vec<String>) -> Option<CommandReply>;
+}
+
+impl<F> ReplyingCommandHandler for F where F: FnMut(&CommandContext, &Vec<String>) -> Option<CommandReply> {
+    fn handle(&mut self, ctx: &CommandContext, args: &Vec<String>) -> Option<CommandReply> {
+        self(ctx, args)
+    }
+}
+
+/// A trait implemented by types that can handle commands off the bot's event loop. The future
+/// that's returned is run on a thread pool, and any error it ends with is logged.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate futures;
+/// # extern crate slackbot;
+/// # fn main() {
+/// # use slackbot::{AsyncSender, AsyncCommandHandler, BotError};
+/// use futures::Future;
+///
+/// struct WeatherCommandHandler;
+///
+/// impl AsyncCommandHandler for WeatherCommandHandler {
+///     fn handle(&mut self, sender: AsyncSender, args: Vec<String>) -> Box<Future<Item=(), Error=BotError> + Send> {
+///         Box::new(futures::future::lazy(move || sender.respond_in_channel("It's sunny!")))
+///     }
+/// }
+/// # }
+/// ```
+pub trait AsyncCommandHandler {
+    /// Start handling the command.
+    fn handle(&mut self, sender: AsyncSender, args: Vec<String>) -> Box<Future<Item=(), Error=BotError> + Send>;
+}
+
+impl<F, R> AsyncCommandHandler for F
+    where F: FnMut(AsyncSender, Vec<String>) -> R,
+          R: IntoFuture<Item=(), Error=BotError>,
+          R::Future: Send + 'static {
+
+    fn handle(&mut self, sender: AsyncSender, args: Vec<String>) -> Box<Future<Item=(), Error=BotError> + Send> {
+        Box::new(self(sender, args).into_future())
+    }
+}
+
+/// Lets a `CommandHandler` be used wherever handlers need to take the bot's state.
+struct Stateless(Box<CommandHandler + Send>);
+
+impl<T> StatefulCommandHandler<T> for Stateless {
+    fn handle(&mut self, _: &mut T, ctx: &mut CommandContext, args: &Vec<String>) {
+        self.0.handle(ctx, args);
+    }
 
-impl<F> CommandHandler for F where F: FnMut(&mut Sender, &Vec<String>) {
-    fn handle(&mut self, sender: &mut Sender, args: &Vec<String>) {
-        self(sender, args);
+    fn without_state(&mut self) -> Option<&mut CommandHandler> {
+        Some(&mut *self.0)
     }
 }
@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+/// Something that happened while a bot was running, for anything watching it from outside, like
+/// a dashboard.
+#[derive(Clone, Debug)]
+pub enum BotEvent {
+    /// The bot connected to Slack.
+    Connected,
+
+    /// The bot's connection to Slack closed.
+    Disconnected,
+
+    /// Someone sent the bot a command. Unknown commands, and ones that get stopped before their
+    /// handler runs (like by middleware or channel restrictions), don't complete.
+    CommandReceived {
+        command: String,
+        user_id: String,
+        channel_id: String
+    },
+
+    /// A command's handler finished. Commands fail when their handler returns an error or marks
+    /// the command with `CommandContext::fail`. Async commands complete when their future does,
+    /// and fail when it ends with an error.
+    CommandCompleted {
+        command: String,
+        duration: Duration,
+        success: bool
+    },
+
+    /// A command's handler has been running for longer than the bot's handler timeout. It
+    /// carries on, and still completes if it finishes, except on worker threads, where it's
+    /// abandoned and completes as having failed right after this.
+    CommandOverran {
+        command: String,
+        timeout: Duration
+    }
+}
+
+/// Everyone listening for a bot's events, shared with the threads async commands finish on.
+#[derive(Clone)]
+pub struct Subscribers {
+    senders: Arc<Mutex<Vec<Sender<BotEvent>>>>
+}
+
+impl Subscribers {
+    pub fn new() -> Self {
+        Subscribers {
+            senders: Arc::new(Mutex::new(Vec::new()))
+        }
+    }
+
+    pub fn subscribe(&self) -> Receiver<BotEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.senders.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Send an event to every subscriber, forgetting the ones that have stopped listening.
+    pub fn send(&self, event: BotEvent) {
+        self.senders.lock().unwrap().retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use slack::User;
+use serde_json::Value;
+
+use super::api;
+use super::error::BotError;
+
+/// How long to wait before asking Slack again about a user it couldn't tell us about, so a flood
+/// of messages from them doesn't start a request each.
+const FAILED_LOOKUP_RETRY_SECS: u64 = 300;
+
+/// Asks Slack about users the bot doesn't know, on threads of their own, so a slow `users.info`
+/// doesn't hold up the event loop.
+pub struct UserLookups {
+    /// The users being looked up right now, by their id.
+    pending: HashSet<String>,
+
+    /// When asking Slack about each user last failed, by their id.
+    failed: HashMap<String, Instant>,
+    results: Sender<(String, Result<User, BotError>)>,
+    finished: Receiver<(String, Result<User, BotError>)>
+}
+
+impl UserLookups {
+    pub fn new() -> Self {
+        let (results, finished) = mpsc::channel();
+        UserLookups {
+            pending: HashSet::new(),
+            failed: HashMap::new(),
+            results: results,
+            finished: finished
+        }
+    }
+
+    /// Start asking Slack about a user, unless that's already happening or it failed recently.
+    pub fn start(&mut self, token: &str, user_id: &str) {
+        let retry_after = Duration::from_secs(FAILED_LOOKUP_RETRY_SECS);
+        let failed_recently = self.failed.get(user_id).map_or(false, |failed| failed.elapsed() < retry_after);
+        if failed_recently || !self.pending.insert(user_id.to_owned()) {
+            return;
+        }
+
+        let (token, user_id, results) = (token.to_owned(), user_id.to_owned(), self.results.clone());
+        thread::spawn(move || {
+            let user = api::call(&token[..], "users.info", &[("user", &user_id[..])]).and_then(|data| {
+                data.get("user").and_then(parse_user).ok_or_else(|| BotError::Slack("users.info didn't return a user".to_owned()))
+            });
+            let _ = results.send((user_id, user));
+        });
+    }
+
+    /// The users Slack has told us about since this was last called.
+    pub fn finished(&mut self) -> Vec<User> {
+        let mut users = Vec::new();
+        while let Ok((user_id, result)) = self.finished.try_recv() {
+            self.pending.remove(&user_id[..]);
+            match result {
+                Ok(user) => {
+                    self.failed.remove(&user_id[..]);
+                    users.push(user);
+                },
+                Err(err) => {
+                    warn!("Couldn't look up user {}: {}", user_id, err);
+                    self.failed.insert(user_id, Instant::now());
+                }
+            }
+        }
+        users
+    }
+}
+
+/// A user we don't know anything about but their id.
+pub fn unknown_user(user_id: &str) -> User {
+    User {
+        id: user_id.to_owned(),
+        name: user_id.to_owned(),
+        is_admin: None,
+        is_owner: None,
+        is_primary_owner: None,
+        deleted: false,
+        is_bot: false,
+        tz_offset: None
+    }
+}
+
+pub fn parse_user(data: &Value) -> Option<User> {
+    let id = match data.get("id") {
+        Some(&Value::String(ref id)) => id.to_owned(),
+        _ => return None
+    };
+    let name = match data.get("name") {
+        Some(&Value::String(ref name)) => name.to_owned(),
+        _ => return None
+    };
+
+    Some(User {
+        id: id,
+        name: name,
+        is_admin: data.get("is_admin").and_then(Value::as_bool),
+        is_owner: data.get("is_owner").and_then(Value::as_bool),
+        is_primary_owner: data.get("is_primary_owner").and_then(Value::as_bool),
+        deleted: data.get("deleted").and_then(Value::as_bool).unwrap_or(false),
+        is_bot: data.get("is_bot").and_then(Value::as_bool).unwrap_or(false),
+        tz_offset: data.get("tz_offset").and_then(Value::as_i64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::UserLookups;
+
+    #[test]
+    fn doesnt_ask_again_soon_after_failing() {
+        let mut lookups = UserLookups::new();
+        lookups.start("xoxb-invalid", "U123");
+        lookups.start("xoxb-invalid", "U123");
+        assert_eq!(lookups.pending.len(), 1);
+
+        let started = Instant::now();
+        while !lookups.pending.is_empty() && started.elapsed() < Duration::from_secs(30) {
+            assert!(lookups.finished().is_empty());
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(lookups.failed.contains_key("U123"));
+
+        lookups.start("xoxb-invalid", "U123");
+        assert!(lookups.pending.is_empty());
+    }
+}
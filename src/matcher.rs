@@ -0,0 +1,29 @@
+use super::sender::Sender;
+
+/// A trait implemented by types that handle messages matching a pattern.
+///
+/// The handler gets the pattern's capture groups, starting with the whole match. Groups that
+/// didn't take part in the match are empty.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{Sender, MatchHandler};
+/// struct TicketLinker;
+///
+/// impl MatchHandler for TicketLinker {
+///     fn handle(&mut self, sender: &mut Sender, captures: &Vec<String>) {
+///         sender.respond_in_thread(format!("https://jira.example.com/browse/{}", captures[0]));
+///     }
+/// }
+/// ```
+pub trait MatchHandler {
+    /// Handle the matching message.
+    fn handle(&mut self, sender: &mut Sender, captures: &Vec<String>);
+}
+
+impl<F> MatchHandler for F where F: FnMut(&mut Sender, &Vec<String>) {
+    fn handle(&mut self, sender: &mut Sender, captures: &Vec<String>) {
+        self(sender, captures);
+    }
+}
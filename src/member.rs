@@ -0,0 +1,38 @@
+use super::sender::Sender;
+
+/// Someone joining or leaving a channel the bot is in.
+pub struct MemberEvent {
+    /// The id of the user that joined or left.
+    pub user_id: String,
+
+    /// The id of the channel they joined or left.
+    pub channel_id: String
+}
+
+/// A trait implemented by types that handle people joining or leaving channels.
+///
+/// The sender is the user that joined or left, in the channel they joined or left. There's no
+/// message behind the event, so replying in a thread or reacting won't work.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{Sender, MemberEvent, MemberEventHandler};
+/// struct Greeter;
+///
+/// impl MemberEventHandler for Greeter {
+///     fn handle(&mut self, sender: &mut Sender, event: &MemberEvent) {
+///         sender.respond_in_channel_raw(format!("Welcome, <@{}>!", event.user_id));
+///     }
+/// }
+/// ```
+pub trait MemberEventHandler {
+    /// Handle the event.
+    fn handle(&mut self, sender: &mut Sender, event: &MemberEvent);
+}
+
+impl<F> MemberEventHandler for F where F: FnMut(&mut Sender, &MemberEvent) {
+    fn handle(&mut self, sender: &mut Sender, event: &MemberEvent) {
+        self(sender, event);
+    }
+}
@@ -0,0 +1,29 @@
+use super::sender::Sender;
+
+/// A trait implemented by types that see every message in the channels the bot is in, whether
+/// or not it's a command.
+///
+/// The sender is the user that sent the message, in the channel it was sent to.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{Sender, MessageHandler};
+/// struct WordCounter(usize);
+///
+/// impl MessageHandler for WordCounter {
+///     fn handle(&mut self, sender: &mut Sender, text: &str) {
+///         self.0 += text.split_whitespace().count();
+///     }
+/// }
+/// ```
+pub trait MessageHandler {
+    /// Handle the message.
+    fn handle(&mut self, sender: &mut Sender, text: &str);
+}
+
+impl<F> MessageHandler for F where F: FnMut(&mut Sender, &str) {
+    fn handle(&mut self, sender: &mut Sender, text: &str) {
+        self(sender, text);
+    }
+}
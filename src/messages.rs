@@ -0,0 +1,100 @@
+/// The messages a bot sends by itself, like the list `help` replies with or the reply to an
+/// unknown command, for bots that want to word them differently or use another language. See
+/// `SlackBot::with_messages`.
+///
+/// Each message is a template, and the parts in braces, like `{command}`, are filled in when it's
+/// sent. The default messages are the ones the bot sends without any of its own.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::Messages;
+/// let messages = Messages {
+///     unknown_command: "Je ne connais pas `{command}`".to_owned(),
+///     rate_limited: "Doucement !".to_owned(),
+///     ..Messages::default()
+/// };
+/// ```
+#[derive(Clone, Debug)]
+pub struct Messages {
+    /// What `help` starts its list of commands with.
+    pub help_header: String,
+
+    /// What the admin command starts its list of all `{count}` commands with.
+    pub admin_header: String,
+
+    /// The reply to an unknown `{command}`, when there's nothing better to suggest.
+    pub unknown_command: String,
+
+    /// The reply to an unknown `{command}` that's a typo of the command `{suggestion}`.
+    pub unknown_command_suggestion: String,
+
+    /// The reply to an unknown `{command}`, pointing at `help` with the bot's `{prefix}`.
+    pub unknown_command_help: String,
+
+    /// The reply to a `{command}` sent in a channel it isn't allowed in.
+    pub wrong_channel: String,
+
+    /// The reply to a `{command}` the sender isn't authorized for.
+    pub not_authorized: String,
+
+    /// The reply to a `{command}` that's cooling down, for `{seconds}` more seconds.
+    pub cooling_down: String,
+
+    /// The reply to someone who's sending commands faster than the bot's rate limit.
+    pub rate_limited: String,
+
+    /// The reply to a `{command}` whose handler is still busy on a worker thread.
+    pub busy: String,
+
+    /// The reply to a message over the bot's `{limit}` in bytes.
+    pub message_too_long: String,
+
+    /// The reply to a command with more arguments than the bot's `{limit}`.
+    pub too_many_args: String
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Messages {
+            help_header: "Available commands:".to_owned(),
+            admin_header: "All commands ({count}):".to_owned(),
+            unknown_command: "Unknown command `{command}`".to_owned(),
+            unknown_command_suggestion: "Unknown command `{command}`, did you mean `{suggestion}`?".to_owned(),
+            unknown_command_help: "Unknown command `{command}`, try `{prefix} help`".to_owned(),
+            wrong_channel: "`{command}` can't be used in this channel".to_owned(),
+            not_authorized: "Sorry, you aren't allowed to use `{command}`".to_owned(),
+            cooling_down: "`{command}` was used recently, try again in {seconds} seconds".to_owned(),
+            rate_limited: "Slow down! You're sending commands too quickly.".to_owned(),
+            busy: "I'm still working on the last `{command}`, try again in a moment.".to_owned(),
+            message_too_long: "That message is too long, the limit is {limit} bytes".to_owned(),
+            too_many_args: "That's too many arguments, the limit is {limit}".to_owned()
+        }
+    }
+}
+
+/// Fill in the parts of a template named in braces. Parts without a value are left as they are,
+/// and so are braces in the values, so a command can't fill in parts of its own.
+pub fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut message = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let part = after.find('}').and_then(|end| {
+            values.iter().find(|&&(name, _)| name == &after[..end]).map(|&(_, value)| (value, end))
+        });
+        match part {
+            Some((value, end)) => {
+                message.push_str(value);
+                rest = &after[end + 1..];
+            },
+            None => {
+                message.push('{');
+                rest = after;
+            }
+        }
+    }
+    message.push_str(rest);
+    message
+}
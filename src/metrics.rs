@@ -0,0 +1,69 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+
+use super::error::BotError;
+
+/// A trait implemented by types that keep track of how long commands take and how often they're
+/// used. Async commands finish on other threads, so metrics have to be `Send` and `Sync`.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use slackbot::Metrics;
+/// struct LogTimings;
+///
+/// impl Metrics for LogTimings {
+///     fn record(&self, command: &str, duration: Duration, success: bool) {
+///         println!("{} took {:?} (succeeded: {})", command, duration, success);
+///     }
+/// }
+/// ```
+pub trait Metrics: Send + Sync {
+    /// Called after each command is handled. Commands fail when their handler panics, returns an
+    /// error, or marks the command with `CommandContext::fail`, or for async commands, when their
+    /// future ends with an error.
+    fn record(&self, command: &str, duration: Duration, success: bool);
+}
+
+impl<F> Metrics for F where F: Fn(&str, Duration, bool) + Send + Sync {
+    fn record(&self, command: &str, duration: Duration, success: bool) {
+        self(command, duration, success);
+    }
+}
+
+/// Handle a command, recording how it went, and return whether it succeeded. The handler says
+/// whether it did, and fails if it panics. Without metrics, the handler is just called.
+pub fn time<F: FnOnce() -> bool>(metrics: Option<Arc<Metrics>>, command: &str, handle: F) -> bool {
+    let metrics = match metrics {
+        Some(metrics) => metrics,
+        None => return handle()
+    };
+
+    let started = Instant::now();
+    let result = panic::catch_unwind(AssertUnwindSafe(handle));
+    metrics.record(command, started.elapsed(), result.as_ref().map_or(false, |&success| success));
+    match result {
+        Ok(success) => success,
+        Err(panic) => panic::resume_unwind(panic)
+    }
+}
+
+/// Record how an async command went once its future finishes, timed from when it was started.
+pub fn time_async(metrics: Option<Arc<Metrics>>, command: String, future: Box<Future<Item=(), Error=BotError> + Send>)
+    -> Box<Future<Item=(), Error=BotError> + Send> {
+
+    let metrics = match metrics {
+        Some(metrics) => metrics,
+        None => return future
+    };
+
+    let started = Instant::now();
+    Box::new(future.then(move |result| {
+        metrics.record(&command[..], started.elapsed(), result.is_ok());
+        result
+    }))
+}
@@ -0,0 +1,58 @@
+use super::sender::CommandContext;
+
+/// A trait implemented by types that run before every command, for things like logging or
+/// metrics that shouldn't have to be repeated in each handler.
+///
+/// Middleware can also change which command is handled, and with what arguments, by changing
+/// the context's `command` and `args`. Middleware runs in the order it was added, and each sees
+/// the changes made by the middleware before it. Once all of it has run, the bot looks up the
+/// command it ended up with, aliases and all, and checks its channels, authorizer, arguments
+/// and cooldown as if that was what was sent.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{Middleware, CommandContext};
+/// struct LogCommands;
+///
+/// impl Middleware for LogCommands {
+///     fn before(&mut self, ctx: &mut CommandContext) -> bool {
+///         println!("{} ran {}", ctx.user.name, ctx.command);
+///         true
+///     }
+/// }
+/// ```
+///
+/// Expanding a macro into a command with arguments:
+///
+/// ```
+/// # use slackbot::{Middleware, CommandContext};
+/// struct ShipIt;
+///
+/// impl Middleware for ShipIt {
+///     fn before(&mut self, ctx: &mut CommandContext) -> bool {
+///         if ctx.command == "shipit" {
+///             ctx.command = "deploy".to_owned();
+///             ctx.args.insert(0, "production".to_owned());
+///         }
+///         true
+///     }
+/// }
+///
+/// let mut ctx = CommandContext::test();
+/// ctx.command = "shipit".to_owned();
+/// ShipIt.before(&mut ctx);
+/// assert_eq!(ctx.command, "deploy");
+/// assert_eq!(ctx.args, vec!["production".to_owned()]);
+/// ```
+pub trait Middleware {
+    /// Called before a command is handled. Return `false` to stop the command from being handled
+    /// at all, including by any middleware after this one.
+    fn before(&mut self, ctx: &mut CommandContext) -> bool;
+}
+
+impl<F> Middleware for F where F: FnMut(&CommandContext) -> bool {
+    fn before(&mut self, ctx: &mut CommandContext) -> bool {
+        self(ctx)
+    }
+}
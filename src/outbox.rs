@@ -0,0 +1,420 @@
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Poll};
+use futures::future;
+use futures::sync::oneshot;
+
+use super::api;
+use super::error::BotError;
+
+/// How long messages to the same channel are kept apart by default. Slack allows bots about one
+/// message a second in each channel.
+pub const DEFAULT_MESSAGE_INTERVAL_MILLIS: u64 = 1000;
+
+/// How many messages can wait to be sent to one channel. Queueing another one fails until there's
+/// room, rather than holding up whatever's sending, which is often the bot's event loop.
+const MAX_QUEUED_PER_CHANNEL: usize = 100;
+
+/// How long the sender thread waits for something to send before checking whether its outbox is
+/// still around.
+const IDLE_CHECK_MILLIS: u64 = 1000;
+
+/// A message waiting its turn to be sent by the bot. It's sent whether or not anything waits for
+/// it, and once it has been, gives back the sent message or why it couldn't be sent. It can be
+/// waited on, or used as a future.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{SlackBot, CommandContext};
+/// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///     // Waiting isn't needed just to send something, only to find out how it went
+///     if let Ok(sent) = ctx.respond_in_channel("Deploying...").wait() {
+///         // ...deploy...
+///         ctx.update_message(&sent.ts, "Deploying... done!");
+///     }
+/// }));
+/// ```
+pub struct Queued<T> {
+    future: Box<Future<Item=T, Error=BotError> + Send>
+}
+
+impl<T: Send + 'static> Queued<T> {
+    pub fn new<F: Future<Item=T, Error=BotError> + Send + 'static>(future: F) -> Self {
+        Queued {
+            future: Box::new(future)
+        }
+    }
+
+    /// Something that's already been sent, or couldn't be queued at all.
+    pub fn ready(result: Result<T, BotError>) -> Self {
+        Queued::new(future::result(result))
+    }
+
+    /// Wait until it's been sent, giving back how sending went.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let mut ctx = CommandContext::test();
+    /// let sent = ctx.respond_in_channel("Hello!").wait().unwrap();
+    /// assert_eq!(sent.channel, "C0TEST");
+    /// ```
+    pub fn wait(self) -> Result<T, BotError> {
+        self.future.wait()
+    }
+}
+
+impl<T> Future for Queued<T> {
+    type Item = T;
+    type Error = BotError;
+
+    fn poll(&mut self) -> Poll<T, BotError> {
+        self.future.poll()
+    }
+}
+
+/// Where a bot's messages wait their turn to be sent, along with everything else it writes to
+/// Slack, like reactions, updates and uploads. Messages to the same channel are sent one at a time
+/// by the outbox's own thread, in the order they were queued, and with some time between them, so
+/// they show up in order and don't get the bot rate limited. Queueing a message doesn't wait for
+/// it to be sent, so the bot's event loop isn't held up by a busy channel.
+#[derive(Clone)]
+pub struct Outbox {
+    shared: Arc<Shared>
+}
+
+struct Shared {
+    queues: Mutex<Queues>,
+
+    /// Notified when a message is queued.
+    changed: Condvar
+}
+
+struct Queues {
+    channels: HashMap<String, Queue>,
+    interval: Duration,
+
+    /// Counts every message queued, so the one that's waited longest can be sent first when more
+    /// than one channel's ready.
+    queued: u64,
+    sender_started: bool
+}
+
+/// Something to send to a channel, numbered in the order it was queued.
+struct Job {
+    number: u64,
+    attempts: u32,
+
+    /// When it can be tried again, after failing with an error that might go away.
+    not_before: Option<Instant>,
+    send: Box<Attempt>
+}
+
+/// Sending something, which can be tried more than once.
+trait Attempt: Send {
+    /// Try sending it, giving back the result if it worked.
+    fn attempt(&mut self) -> Result<(), BotError>;
+
+    /// Give up, with why it couldn't be sent.
+    fn fail(&mut self, err: BotError);
+}
+
+struct Pending<T, F> {
+    send: F,
+    done: Option<oneshot::Sender<Result<T, BotError>>>
+}
+
+impl<T, F> Attempt for Pending<T, F> where T: Send, F: FnMut() -> Result<T, BotError> + Send {
+    fn attempt(&mut self) -> Result<(), BotError> {
+        let sent = try!((self.send)());
+        if let Some(done) = self.done.take() {
+            let _ = done.send(Ok(sent));
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, err: BotError) {
+        if let Some(done) = self.done.take() {
+            let _ = done.send(Err(err));
+        }
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    last_sent: Option<Instant>
+}
+
+impl Queues {
+    /// The channel whose next message should be sent now, or how long until one can be.
+    fn next(&self) -> Result<String, Option<Duration>> {
+        let mut ready: Option<(u64, &String)> = None;
+        let mut soonest: Option<Duration> = None;
+        let now = Instant::now();
+        for (channel_id, queue) in self.channels.iter() {
+            let job = match queue.jobs.front() {
+                Some(job) => job,
+                None => continue
+            };
+            let apart = queue.last_sent.map_or(now, |last_sent| last_sent + self.interval);
+            let ready_at = job.not_before.map_or(apart, |not_before| cmp::max(apart, not_before));
+            if ready_at > now {
+                let wait = ready_at - now;
+                soonest = Some(soonest.map_or(wait, |soonest| cmp::min(wait, soonest)));
+            } else if ready.map_or(true, |(first, _)| job.number < first) {
+                ready = Some((job.number, channel_id));
+            }
+        }
+        match ready {
+            Some((_, channel_id)) => Ok(channel_id.clone()),
+            None => Err(soonest)
+        }
+    }
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        let queues = Queues {
+            channels: HashMap::new(),
+            interval: Duration::from_millis(DEFAULT_MESSAGE_INTERVAL_MILLIS),
+            queued: 0,
+            sender_started: false
+        };
+        Outbox {
+            shared: Arc::new(Shared {
+                queues: Mutex::new(queues),
+                changed: Condvar::new()
+            })
+        }
+    }
+
+    /// Change how long messages to the same channel are kept apart, including for messages that
+    /// are already waiting.
+    pub fn set_interval(&self, interval: Duration) {
+        self.shared.queues.lock().unwrap().interval = interval;
+    }
+
+    /// Queue something to send to a channel once it's its turn, returning a handle for what
+    /// sending gave back. If it fails with an error that might go away, like being rate limited,
+    /// it's tried again after a while, still ahead of the channel's other messages. Other
+    /// channels' messages aren't held up while it waits.
+    pub fn send<T, F>(&self, channel_id: &str, send: F) -> Queued<T>
+        where T: Send + 'static, F: FnMut() -> Result<T, BotError> + Send + 'static {
+
+        let (done, result) = oneshot::channel();
+        let pending = Pending {
+            send: send,
+            done: Some(done)
+        };
+        if let Err(err) = self.queue(channel_id, Box::new(pending)) {
+            return Queued::ready(Err(err));
+        }
+        Queued::new(result.then(|result| match result {
+            Ok(result) => result,
+            // The only way the result goes missing is if sending panicked
+            Err(_) => Err(BotError::Slack("Sending the message failed unexpectedly".to_owned()))
+        }))
+    }
+
+    fn queue(&self, channel_id: &str, send: Box<Attempt>) -> Result<(), BotError> {
+        let mut queues = self.shared.queues.lock().unwrap();
+        if queues.channels.get(channel_id).map_or(false, |queue| queue.jobs.len() >= MAX_QUEUED_PER_CHANNEL) {
+            return Err(BotError::Slack(format!("Too many messages are waiting to be sent to {}", channel_id)));
+        }
+
+        let number = queues.queued;
+        queues.queued += 1;
+        queues.channels.entry(channel_id.to_owned()).or_insert_with(Queue::default).jobs.push_back(Job {
+            number: number,
+            attempts: 0,
+            not_before: None,
+            send: send
+        });
+        // Outboxes that never send anything, like the ones test contexts have, don't need a thread
+        if !queues.sender_started {
+            queues.sender_started = true;
+            let shared = self.shared.clone();
+            thread::spawn(move || run_sender(shared));
+        }
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+}
+
+/// Send queued messages as their turns come, until the outbox is dropped.
+fn run_sender(shared: Arc<Shared>) {
+    loop {
+        let job = {
+            let mut queues = shared.queues.lock().unwrap();
+            loop {
+                // Channels are forgotten once they're quiet, so they don't pile up
+                let interval = queues.interval;
+                queues.channels.retain(|_, queue| {
+                    !queue.jobs.is_empty() || queue.last_sent.map_or(false, |last_sent| last_sent.elapsed() < interval)
+                });
+                match queues.next() {
+                    Ok(channel_id) => {
+                        let job = queues.channels.get_mut(&channel_id).and_then(|queue| queue.jobs.pop_front());
+                        break (channel_id, job);
+                    },
+                    Err(Some(wait)) => queues = shared.changed.wait_timeout(queues, wait).unwrap().0,
+                    Err(None) => {
+                        // Only this thread is left holding the outbox, so nothing else can be queued
+                        if Arc::strong_count(&shared) == 1 {
+                            return;
+                        }
+                        queues = shared.changed.wait_timeout(queues, Duration::from_millis(IDLE_CHECK_MILLIS)).unwrap().0;
+                    }
+                }
+            }
+        };
+
+        if let (channel_id, Some(mut job)) = job {
+            job.attempts += 1;
+            let result = {
+                let send = &mut job.send;
+                panic::catch_unwind(AssertUnwindSafe(|| send.attempt()))
+            };
+            let retry = match result {
+                Ok(Ok(())) => None,
+                Ok(Err(err)) => match api::retry_delay(&err, job.attempts) {
+                    Some(delay) => {
+                        debug!("Sending to {} again after a transient error: {}", channel_id, err);
+                        job.not_before = Some(Instant::now() + delay);
+                        Some(job)
+                    },
+                    None => {
+                        job.send.fail(err);
+                        None
+                    }
+                },
+                // A send that panics loses its own message, but not the rest of the queue
+                Err(_) => {
+                    warn!("Sending a message to {} panicked", channel_id);
+                    None
+                }
+            };
+
+            let mut queues = shared.queues.lock().unwrap();
+            let queue = queues.channels.entry(channel_id).or_insert_with(Queue::default);
+            queue.last_sent = Some(Instant::now());
+            if let Some(job) = retry {
+                queue.jobs.push_front(job);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use super::Outbox;
+    use super::super::error::BotError;
+
+    #[test]
+    fn sends_a_channels_messages_in_order() {
+        let outbox = Outbox::new();
+        outbox.set_interval(Duration::from_millis(0));
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let queued = (0..10).map(|i| {
+            let sent = sent.clone();
+            outbox.send("C1", move || {
+                sent.lock().unwrap().push(i);
+                Ok(i)
+            })
+        }).collect::<Vec<_>>();
+
+        let results = queued.into_iter().map(|queued| queued.wait().unwrap()).collect::<Vec<_>>();
+        assert_eq!(results, (0..10).collect::<Vec<_>>());
+        assert_eq!(*sent.lock().unwrap(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn keeps_messages_to_a_channel_apart() {
+        let outbox = Outbox::new();
+        outbox.set_interval(Duration::from_millis(50));
+        let started = Instant::now();
+        let first = outbox.send("C1", || Ok(()));
+        let second = outbox.send("C1", || Ok(()));
+        let other = outbox.send("C2", || Ok(()));
+
+        other.wait().unwrap();
+        first.wait().unwrap();
+        assert!(started.elapsed() < Duration::from_millis(50));
+        second.wait().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn queueing_doesnt_wait_for_sending() {
+        let outbox = Outbox::new();
+        let started = Instant::now();
+        let queued = (0..3).map(|_| outbox.send("C1", || Ok(()))).collect::<Vec<_>>();
+        assert!(started.elapsed() < Duration::from_millis(500));
+        drop(queued);
+    }
+
+    #[test]
+    fn a_send_that_panics_fails_without_stopping_the_rest() {
+        let outbox = Outbox::new();
+        outbox.set_interval(Duration::from_millis(0));
+        let panicked = outbox.send("C1", || -> Result<(), _> { panic!("Couldn't send") });
+        let next = outbox.send("C1", || Ok("sent"));
+        assert!(panicked.wait().is_err());
+        assert_eq!(next.wait().unwrap(), "sent");
+    }
+
+    #[test]
+    fn tries_again_without_holding_up_other_channels() {
+        let outbox = Outbox::new();
+        outbox.set_interval(Duration::from_millis(0));
+        let started = Instant::now();
+        let mut attempts = 0;
+        let flaky = outbox.send("C1", move || {
+            attempts += 1;
+            if attempts == 1 {
+                Err(BotError::Network("Connection reset".to_owned()))
+            } else {
+                Ok(attempts)
+            }
+        });
+        let other = outbox.send("C2", || Ok(()));
+
+        other.wait().unwrap();
+        assert!(started.elapsed() < Duration::from_millis(250));
+        assert_eq!(flaky.wait().unwrap(), 2);
+    }
+
+    #[test]
+    fn doesnt_try_again_after_errors_that_wont_go_away() {
+        let outbox = Outbox::new();
+        let sent = outbox.send("C1", || -> Result<(), _> {
+            Err(BotError::Api { method: "chat.postMessage".to_owned(), error: "channel_not_found".to_owned() })
+        });
+        assert!(sent.wait().is_err());
+    }
+
+    #[test]
+    fn fails_rather_than_waiting_when_a_channel_is_full() {
+        let outbox = Outbox::new();
+        outbox.set_interval(Duration::from_secs(60));
+        let started = Instant::now();
+        let queued = (0..200).map(|_| outbox.send("C1", || Ok(()))).collect::<Vec<_>>();
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        let other = outbox.send("C2", || Ok("sent"));
+        assert_eq!(other.wait().unwrap(), "sent");
+        assert!(queued.into_iter().last().unwrap().wait().is_err());
+    }
+}
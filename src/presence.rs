@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Whether a user is around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Presence {
+    Active,
+    Away
+}
+
+impl Presence {
+    /// The presence Slack describes as `active` or `away`.
+    pub fn parse(presence: &str) -> Option<Self> {
+        match presence {
+            "active" => Some(Presence::Active),
+            "away" => Some(Presence::Away),
+            _ => None
+        }
+    }
+}
+
+/// The last presence Slack told the bot about for each user, by id.
+pub type Presences = Arc<Mutex<HashMap<String, Presence>>>;
@@ -0,0 +1,26 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A small xorshift random number generator. It's nowhere near good enough for anything secret,
+/// but it's plenty for picking a reply.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero
+        Rng(if seed == 0 { 0x9E3779B97F4A7C15 } else { seed })
+    }
+
+    /// A generator seeded from the clock.
+    pub fn from_time() -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0));
+        Rng::new(now.as_secs() ^ ((now.subsec_nanos() as u64) << 32))
+    }
+
+    /// A number from 0 up to but not including `n`, which can't be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
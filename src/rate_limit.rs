@@ -0,0 +1,90 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Limits how often each user can run commands, over a sliding one minute window.
+pub struct RateLimiter {
+    max_per_minute: u32,
+    invocations: HashMap<String, VecDeque<Instant>>
+}
+
+impl RateLimiter {
+    pub fn new(max_per_minute: u32) -> Self {
+        RateLimiter {
+            max_per_minute: max_per_minute,
+            invocations: HashMap::new()
+        }
+    }
+
+    /// Record that the user is trying to run a command, returning whether they're allowed to.
+    pub fn allow(&mut self, user_id: &str) -> bool {
+        let now = Instant::now();
+        let window = Duration::from_secs(60);
+
+        let invocations = self.invocations.entry(user_id.to_owned()).or_insert_with(VecDeque::new);
+        while invocations.front().map_or(false, |&time| now.duration_since(time) >= window) {
+            invocations.pop_front();
+        }
+
+        if invocations.len() >= self.max_per_minute as usize {
+            false
+        } else {
+            invocations.push_back(now);
+            true
+        }
+    }
+}
+
+/// Limits how often each command can run, across everyone that uses it.
+pub struct Cooldowns {
+    last_run: HashMap<String, Instant>
+}
+
+impl Cooldowns {
+    pub fn new() -> Self {
+        Cooldowns {
+            last_run: HashMap::new()
+        }
+    }
+
+    /// How long is left of a command's cooldown, if it can't run yet.
+    pub fn remaining(&self, command_name: &str, cooldown: Duration) -> Option<Duration> {
+        self.last_run.get(command_name).and_then(|&last_run| {
+            let elapsed = last_run.elapsed();
+            if elapsed < cooldown { Some(cooldown - elapsed) } else { None }
+        })
+    }
+
+    /// Record that a command has started running, starting its cooldown again.
+    pub fn start(&mut self, command_name: &str) {
+        self.last_run.insert(command_name.to_owned(), Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Cooldowns, RateLimiter};
+
+    #[test]
+    fn limits_each_user() {
+        let mut limiter = RateLimiter::new(2);
+        assert!(limiter.allow("U1"));
+        assert!(limiter.allow("U1"));
+        assert!(!limiter.allow("U1"));
+        assert!(limiter.allow("U2"));
+    }
+
+    #[test]
+    fn cooldowns_only_start_when_a_command_runs() {
+        let mut cooldowns = Cooldowns::new();
+        let cooldown = Duration::from_secs(60);
+        assert_eq!(cooldowns.remaining("deploy", cooldown), None);
+        assert_eq!(cooldowns.remaining("deploy", cooldown), None);
+
+        cooldowns.start("deploy");
+        assert!(cooldowns.remaining("deploy", cooldown).map_or(false, |remaining| remaining <= cooldown));
+        assert_eq!(cooldowns.remaining("deploy", Duration::from_secs(0)), None);
+        assert_eq!(cooldowns.remaining("status", cooldown), None);
+    }
+}
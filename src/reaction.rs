@@ -0,0 +1,41 @@
+use super::sender::Sender;
+
+/// An emoji reaction that was added to or removed from a message.
+pub struct Reaction {
+    /// The name of the emoji, without colons (e.g. `pushpin`).
+    pub emoji: String,
+
+    /// Whether the reaction was added, rather than removed.
+    pub added: bool
+}
+
+/// A trait implemented by types that handle reactions to messages in channels the bot is in.
+///
+/// The sender is the user who reacted, and its channel and timestamp are those of the message
+/// that was reacted to, so `respond_in_thread` replies to that message.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{Sender, Reaction, ReactionHandler};
+/// struct PinCounter(u32);
+///
+/// impl ReactionHandler for PinCounter {
+///     fn handle(&mut self, sender: &mut Sender, reaction: &Reaction) {
+///         if reaction.emoji == "pushpin" && reaction.added {
+///             self.0 += 1;
+///             sender.respond_in_thread(format!("That's pin number {}!", self.0));
+///         }
+///     }
+/// }
+/// ```
+pub trait ReactionHandler {
+    /// Handle the reaction.
+    fn handle(&mut self, sender: &mut Sender, reaction: &Reaction);
+}
+
+impl<F> ReactionHandler for F where F: FnMut(&mut Sender, &Reaction) {
+    fn handle(&mut self, sender: &mut Sender, reaction: &Reaction) {
+        self(sender, reaction);
+    }
+}
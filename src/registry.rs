@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::{CommandHandler, StatefulCommandHandler, Stateless};
+
+/// A command handler that can be handed between threads.
+pub type SendCommandHandler<T> = Box<StatefulCommandHandler<T> + Send>;
+
+/// The handlers for a bot's regular commands.
+pub struct Commands<T> {
+    // A handler is taken out of its slot while it's handling a command, so that it can change
+    // the bot's commands without waiting on itself
+    handlers: HashMap<String, Option<SendCommandHandler<T>>>
+}
+
+impl<T> Commands<T> {
+    pub fn new() -> Self {
+        Commands {
+            handlers: HashMap::new()
+        }
+    }
+
+    pub fn contains(&self, command_name: &str) -> bool {
+        self.handlers.contains_key(command_name)
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        self.handlers.keys().collect()
+    }
+
+    pub fn insert(&mut self, command_name: String, handler: SendCommandHandler<T>) {
+        self.handlers.insert(command_name, Some(handler));
+    }
+
+    pub fn remove(&mut self, command_name: &str) -> Option<SendCommandHandler<T>> {
+        self.handlers.remove(command_name).and_then(|handler| handler)
+    }
+
+    /// Take a command's handler out to handle a command with.
+    pub fn take(&mut self, command_name: &str) -> Option<SendCommandHandler<T>> {
+        self.handlers.get_mut(command_name).and_then(|handler| handler.take())
+    }
+
+    /// Put a handler back after it's handled a command, unless its command was replaced or removed
+    /// in the meantime.
+    pub fn put_back(&mut self, command_name: &str, handler: SendCommandHandler<T>) {
+        if let Some(slot) = self.handlers.get_mut(command_name) {
+            if slot.is_none() {
+                *slot = Some(handler);
+            }
+        }
+    }
+
+    /// Remove a command whose handler was taken out, unless it was replaced in the meantime.
+    /// Returns whether it was removed.
+    pub fn discard(&mut self, command_name: &str) -> bool {
+        let taken = self.handlers.get(command_name).map_or(false, Option::is_none);
+        if taken {
+            self.handlers.remove(command_name);
+        }
+        taken
+    }
+}
+
+/// Put a handler back after it's handled a command, or remove its command if the handler asked to
+/// be removed.
+pub fn return_handler<T>(commands: &Mutex<Commands<T>>, command_names: &Mutex<Vec<String>>, command_name: &str,
+                         handler: SendCommandHandler<T>, deregistered: bool) {
+    if deregistered {
+        if commands.lock().unwrap().discard(command_name) {
+            debug!("Command {} removed itself", command_name);
+            command_names.lock().unwrap().retain(|name| name != command_name);
+        }
+    } else {
+        commands.lock().unwrap().put_back(command_name, handler);
+    }
+}
+
+/// A handle for changing a bot's commands from anywhere, including other threads and command
+/// handlers, while the bot is running.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{SlackBot, CommandContext};
+/// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// let registry = my_bot.command_registry();
+/// my_bot.on("enable-beta", Box::new(move |ctx: &mut CommandContext, args: &Vec<String>| {
+///     registry.on("beta", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///         ctx.respond_in_channel("Welcome to the beta!");
+///     }));
+///     ctx.respond_in_channel("The beta is open");
+/// }));
+/// ```
+pub struct CommandRegistry<T = ()> {
+    commands: Arc<Mutex<Commands<T>>>,
+    command_names: Arc<Mutex<Vec<String>>>
+}
+
+impl<T> Clone for CommandRegistry<T> {
+    fn clone(&self) -> Self {
+        CommandRegistry {
+            commands: self.commands.clone(),
+            command_names: self.command_names.clone()
+        }
+    }
+}
+
+impl<T> CommandRegistry<T> {
+    pub fn new(commands: Arc<Mutex<Commands<T>>>, command_names: Arc<Mutex<Vec<String>>>) -> Self {
+        CommandRegistry {
+            commands: commands,
+            command_names: command_names
+        }
+    }
+
+    /// Add a command, replacing any command with the same name. See `SlackBot::on`.
+    pub fn on<S: Into<String>>(&self, command_name: S, handler: Box<CommandHandler + Send>) {
+        self.on_stateful(command_name, Box::new(Stateless(handler)));
+    }
+
+    /// Add a command with a handler that can use the bot's state. See `SlackBot::on_stateful`.
+    pub fn on_stateful<S: Into<String>>(&self, command_name: S, handler: SendCommandHandler<T>) {
+        let command_name = command_name.into();
+        add_name(&self.command_names, &command_name[..]);
+        self.commands.lock().unwrap().insert(command_name, handler);
+    }
+
+    /// Remove a command, giving back its handler. A command that's being handled right now finishes
+    /// first, and its handler isn't given back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// let registry = my_bot.command_registry();
+    /// my_bot.on("close-beta", Box::new(move |ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     registry.off("beta");
+    /// }));
+    /// ```
+    pub fn off(&self, command_name: &str) -> Option<SendCommandHandler<T>> {
+        // Async commands aren't in here, so their names are left alone
+        let handler = {
+            let mut commands = self.commands.lock().unwrap();
+            if !commands.contains(command_name) {
+                return None;
+            }
+            commands.remove(command_name)
+        };
+        self.command_names.lock().unwrap().retain(|name| name != command_name);
+        handler
+    }
+
+    /// The names of every command, in the order they were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("ping", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("pong");
+    /// }));
+    /// assert_eq!(my_bot.command_registry().command_names(), vec!["ping".to_owned()]);
+    /// ```
+    pub fn command_names(&self) -> Vec<String> {
+        self.command_names.lock().unwrap().clone()
+    }
+}
+
+/// The part of a command that a wildcard command name like `git-*` matched, if it matched. The
+/// wildcard has to match at least one character.
+pub fn wildcard_suffix<'a>(pattern: &str, command_name: &'a str) -> Option<&'a str> {
+    let star = match pattern.find('*') {
+        Some(star) => star,
+        None => return None
+    };
+    let (before, after) = (&pattern[..star], &pattern[star + 1..]);
+    if command_name.len() > before.len() + after.len() && command_name.starts_with(before)
+        && command_name.ends_with(after) {
+        Some(&command_name[before.len()..command_name.len() - after.len()])
+    } else {
+        None
+    }
+}
+
+/// Remember a command's name for `help`, unless it's already there.
+pub fn add_name(command_names: &Mutex<Vec<String>>, command_name: &str) {
+    let mut command_names = command_names.lock().unwrap();
+    if !command_names.iter().any(|name| name == command_name) {
+        command_names.push(command_name.to_owned());
+    }
+}
@@ -0,0 +1,55 @@
+use super::{ReplyingCommandHandler, TryCommandHandler};
+use super::attachment::Attachment;
+use super::error::BotError;
+use super::sender::CommandContext;
+
+/// What a command says back, for handlers that give their reply to the bot to send instead of
+/// sending it themselves. See `SlackBot::on_reply`.
+#[derive(Clone, Debug)]
+pub enum CommandReply {
+    /// A message in the channel the command came from.
+    Text(String),
+
+    /// An attachment in the channel the command came from.
+    Attachment(Attachment),
+
+    /// A message only the person who sent the command can see.
+    Ephemeral(String),
+
+    /// A message in the thread the command came from, or a new thread under it.
+    Thread(String)
+}
+
+impl CommandReply {
+    /// Send the reply to a command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{CommandContext, CommandReply};
+    /// let mut ctx = CommandContext::test();
+    /// CommandReply::Thread("On it!".to_owned()).send(&mut ctx).unwrap();
+    /// assert_eq!(ctx.responses(), &["On it!".to_owned()]);
+    /// ```
+    pub fn send(self, ctx: &mut CommandContext) -> Result<(), BotError> {
+        match self {
+            CommandReply::Text(message) => ctx.respond_in_channel(message).wait().map(|_| ()),
+            CommandReply::Attachment(attachment) => ctx.respond_with_attachment(attachment).wait().map(|_| ()),
+            CommandReply::Ephemeral(message) => ctx.respond_ephemeral(message).wait().map(|_| ()),
+            CommandReply::Thread(message) => ctx.respond_in_thread(message).wait().map(|_| ())
+        }
+    }
+}
+
+/// Lets a `ReplyingCommandHandler` be used wherever handlers send their own replies, by sending
+/// the reply it returns.
+pub struct Replying(pub Box<ReplyingCommandHandler + Send>);
+
+impl TryCommandHandler for Replying {
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) -> Result<(), BotError> {
+        match self.0.handle(ctx, args) {
+            Some(reply) => reply.send(ctx),
+            None => Ok(())
+        }
+    }
+}
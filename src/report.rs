@@ -0,0 +1,94 @@
+use std::sync::{Arc, Mutex};
+
+use super::{CommandHandler, TryCommandHandler};
+use super::error::BotError;
+use super::sender::CommandContext;
+
+/// A trait implemented by types that deal with the errors command handlers return, so each
+/// handler doesn't have to tell users what went wrong itself.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{CommandContext, ErrorReporter, BotError};
+/// struct ReplyWithError;
+///
+/// impl ErrorReporter for ReplyWithError {
+///     fn report(&mut self, ctx: &mut CommandContext, command: &str, err: &BotError) {
+///         ctx.respond_in_channel(format!("`{}` didn't work: {}", command, err));
+///     }
+/// }
+/// ```
+pub trait ErrorReporter {
+    /// Report an error from a command's handler.
+    fn report(&mut self, ctx: &mut CommandContext, command: &str, err: &BotError);
+}
+
+impl<F> ErrorReporter for F where F: FnMut(&mut CommandContext, &str, &BotError) {
+    fn report(&mut self, ctx: &mut CommandContext, command: &str, err: &BotError) {
+        self(ctx, command, err);
+    }
+}
+
+/// Where a bot's command errors go. Shared by every fallible handler, so the reporter can be
+/// changed after they're added.
+pub type SharedReporter = Arc<Mutex<Option<Box<ErrorReporter + Send>>>>;
+
+/// Lets a `TryCommandHandler` be used wherever handlers can't fail, by reporting its errors.
+pub struct Reported {
+    command_name: String,
+    handler: Box<TryCommandHandler + Send>,
+    reporter: SharedReporter
+}
+
+impl Reported {
+    pub fn new(command_name: String, handler: Box<TryCommandHandler + Send>, reporter: SharedReporter) -> Self {
+        Reported {
+            command_name: command_name,
+            handler: handler,
+            reporter: reporter
+        }
+    }
+}
+
+impl CommandHandler for Reported {
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) {
+        if let Err(err) = self.handler.handle(ctx, args) {
+            ctx.fail();
+            match *self.reporter.lock().unwrap() {
+                Some(ref mut reporter) => reporter.report(ctx, &self.command_name[..], &err),
+                None => warn!("Command {} failed: {}", self.command_name, err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::Reported;
+    use super::super::CommandHandler;
+    use super::super::error::BotError;
+    use super::super::sender::CommandContext;
+
+    fn reported(result: Result<(), BotError>) -> Reported {
+        let mut result = Some(result);
+        let handler = move |_: &mut CommandContext, _: &Vec<String>| result.take().unwrap();
+        Reported::new("deploy".to_owned(), Box::new(handler), Arc::new(Mutex::new(None)))
+    }
+
+    #[test]
+    fn errors_fail_the_command() {
+        let mut ctx = CommandContext::test();
+        reported(Err(BotError::Command("nothing to deploy".to_owned()))).handle(&mut ctx, &vec![]);
+        assert!(ctx.failed());
+    }
+
+    #[test]
+    fn successes_dont_fail_the_command() {
+        let mut ctx = CommandContext::test();
+        reported(Ok(())).handle(&mut ctx, &vec![]);
+        assert!(!ctx.failed());
+    }
+}
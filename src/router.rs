@@ -0,0 +1,74 @@
+use super::CommandHandler;
+use super::sender::CommandContext;
+
+/// A command handler that passes commands on to subcommands, so that `deploy start prod` and
+/// `deploy stop prod` can have handlers of their own.
+///
+/// Subcommands are matched against the command's leading arguments, and the longest match wins.
+/// Its handler gets whatever arguments are left over.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{SlackBot, CommandContext, CommandRouter};
+/// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// let mut deploy = CommandRouter::new();
+/// deploy.on(&["start"], Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///     ctx.respond_in_channel(format!("Deploying to {}...", args.join(" ")));
+/// }));
+/// deploy.on(&["start", "prod"], Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+///     ctx.respond_in_channel("Deploying to prod, fingers crossed...");
+/// }));
+///
+/// my_bot.on("deploy", Box::new(deploy));
+/// ```
+pub struct CommandRouter {
+    routes: Vec<(Vec<String>, Box<CommandHandler + Send>)>
+}
+
+impl CommandRouter {
+    pub fn new() -> Self {
+        CommandRouter {
+            routes: Vec::new()
+        }
+    }
+
+    /// Handle a subcommand, given as the words that make it up. A subcommand with no words
+    /// handles anything that doesn't match another subcommand.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{CommandContext, CommandRouter};
+    /// let mut deploy = CommandRouter::new();
+    /// deploy.on(&["stop"], Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("Stopping the deploy");
+    /// }));
+    /// ```
+    pub fn on<S: AsRef<str>>(&mut self, path: &[S], handler: Box<CommandHandler + Send>) {
+        let path = path.iter().map(|word| word.as_ref().to_owned()).collect::<Vec<_>>();
+        self.routes.retain(|&(ref route, _)| *route != path);
+        self.routes.push((path, handler));
+    }
+}
+
+impl CommandHandler for CommandRouter {
+    fn handle(&mut self, ctx: &mut CommandContext, args: &Vec<String>) {
+        let best = self.routes.iter_mut()
+            .filter(|&&mut (ref route, _)| route.len() <= args.len() && route[..] == args[..route.len()])
+            .max_by_key(|&&mut (ref route, _)| route.len());
+
+        match best {
+            Some(&mut (ref route, ref mut handler)) => {
+                let rest = args[route.len()..].to_vec();
+                handler.handle(ctx, &rest);
+            },
+            None => {
+                let subcommands = self.routes.iter()
+                    .map(|&(ref route, _)| format!("`{}`", route.join(" ")))
+                    .collect::<Vec<_>>();
+                let _ = ctx.respond_in_channel(format!("Try one of: {}", subcommands.join(", ")));
+            }
+        }
+    }
+}
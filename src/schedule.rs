@@ -0,0 +1,322 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::api;
+use super::error::BotError;
+use super::outbox::Outbox;
+
+/// Sends messages to the channel a scheduled task was set up for.
+pub struct ChannelSender {
+    token: String,
+    outbox: Outbox,
+
+    /// The channel the task posts to, by id or by name with a leading `#`.
+    pub channel: String
+}
+
+impl ChannelSender {
+    pub fn new<A: Into<String>, B: Into<String>>(token: A, channel: B, outbox: Outbox) -> Self {
+        ChannelSender {
+            token: token.into(),
+            outbox: outbox,
+            channel: channel.into()
+        }
+    }
+
+    /// Send a message to the task's channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, ChannelSender};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.schedule("0 17 * * 5", "#general", Box::new(|sender: &mut ChannelSender| {
+    ///     sender.send_message("Have a good weekend!");
+    /// }));
+    /// ```
+    pub fn send_message<S: Into<String>>(&mut self, message: S) -> Result<(), BotError> {
+        let message = message.into();
+        let params = [("channel", &self.channel[..]), ("text", &message[..]), ("as_user", "true")];
+        let call = api::Call::new(&self.token[..], "chat.postMessage", &params);
+        self.outbox.send(&self.channel[..], move || call.send()).wait().map(|_| ())
+    }
+}
+
+/// A trait implemented by types that can be run on a schedule. Tasks run on their own thread, so
+/// they have to be `Send`.
+///
+/// # Examples
+///
+/// ```
+/// # use slackbot::{ChannelSender, ScheduledTask};
+/// struct StandupReminder;
+///
+/// impl ScheduledTask for StandupReminder {
+///     fn run(&mut self, sender: &mut ChannelSender) {
+///         sender.send_message("Standup time!");
+///     }
+/// }
+/// ```
+pub trait ScheduledTask: Send {
+    /// Run the task.
+    fn run(&mut self, sender: &mut ChannelSender);
+}
+
+impl<F> ScheduledTask for F where F: FnMut(&mut ChannelSender) + Send {
+    fn run(&mut self, sender: &mut ChannelSender) {
+        self(sender);
+    }
+}
+
+/// When a cron expression fires, as a set of allowed values for each field.
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days_of_month: Vec<bool>,
+    months: Vec<bool>,
+    days_of_week: Vec<bool>,
+    any_day_of_month: bool,
+    any_day_of_week: bool
+}
+
+impl CronSchedule {
+    /// Parse a standard five field cron expression (minute, hour, day of month, month, day of
+    /// week). Fields can be `*`, numbers, ranges like `1-5`, lists like `1,15` and steps like
+    /// `*/10`. Sunday is 0 or 7.
+    pub fn parse(expr: &str) -> Result<Self, BotError> {
+        let fields = expr.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(BotError::Config(format!("Cron expression `{}` should have 5 fields", expr)));
+        }
+
+        let mut days_of_week = try!(parse_field(fields[4], 0, 7));
+        if days_of_week[7] {
+            days_of_week[0] = true;
+        }
+        days_of_week.truncate(7);
+
+        Ok(CronSchedule {
+            minutes: try!(parse_field(fields[0], 0, 59)),
+            hours: try!(parse_field(fields[1], 0, 23)),
+            days_of_month: try!(parse_field(fields[2], 1, 31)),
+            months: try!(parse_field(fields[3], 1, 12)),
+            days_of_week: days_of_week,
+            any_day_of_month: fields[2] == "*",
+            any_day_of_week: fields[4] == "*"
+        })
+    }
+
+    /// Whether the schedule fires in the given minute, counted from the Unix epoch in UTC.
+    fn fires_at(&self, minute: u64) -> bool {
+        let days = minute / (24 * 60);
+        let hour = (minute / 60 % 24) as usize;
+        let (month, day) = month_and_day(days);
+        // The epoch was a Thursday
+        let weekday = ((days + 4) % 7) as usize;
+
+        // Like cron, when both day fields are restricted either one matching is enough
+        let day_matches = match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => self.days_of_month[day] || self.days_of_week[weekday],
+            _ => self.days_of_month[day] && self.days_of_week[weekday]
+        };
+        self.minutes[(minute % 60) as usize] && self.hours[hour] && self.months[month] && day_matches
+    }
+}
+
+/// Parse one cron field into a table of which values between 0 and `max` are allowed.
+fn parse_field(field: &str, min: usize, max: usize) -> Result<Vec<bool>, BotError> {
+    let invalid = || BotError::Config(format!("Invalid cron field `{}`", field));
+    let mut allowed = vec![false; max + 1];
+
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(i) => (&part[..i], try!(part[i + 1..].parse::<usize>().map_err(|_| invalid()))),
+            None => (part, 1)
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some(i) = range.find('-') {
+            let start = try!(range[..i].parse::<usize>().map_err(|_| invalid()));
+            let end = try!(range[i + 1..].parse::<usize>().map_err(|_| invalid()));
+            (start, end)
+        } else {
+            let value = try!(range.parse::<usize>().map_err(|_| invalid()));
+            (value, if step > 1 { max } else { value })
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..end + 1).filter(|value| (value - start) % step == 0) {
+            allowed[value] = true;
+        }
+    }
+    Ok(allowed)
+}
+
+/// The month (1-12) and day of the month of a day counted from the Unix epoch.
+fn month_and_day(days: u64) -> (usize, usize) {
+    // From Howard Hinnant's civil_from_days, with the year starting in March so leap days come
+    // last
+    let days = days + 719468;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    (month as usize, day as usize)
+}
+
+/// A task and when and where it runs.
+pub struct Job {
+    pub schedule: CronSchedule,
+    pub channel: String,
+    pub task: Box<ScheduledTask>
+}
+
+/// Runs jobs on a background thread while the bot is running.
+pub struct Scheduler {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<Vec<Job>>
+}
+
+impl Scheduler {
+    pub fn start(token: String, outbox: Outbox, mut jobs: Vec<Job>) -> Self {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut last_minute = now().as_secs() / 60;
+            loop {
+                let current = now();
+                let until_next_minute = Duration::from_secs(60 - current.as_secs() % 60) - Duration::new(0, current.subsec_nanos());
+                match stopped.recv_timeout(until_next_minute) {
+                    Err(RecvTimeoutError::Timeout) => {},
+                    _ => break
+                }
+
+                // Waking up early shouldn't run anything twice
+                let minute = now().as_secs() / 60;
+                if minute <= last_minute {
+                    continue;
+                }
+                last_minute = minute;
+
+                for job in jobs.iter_mut().filter(|job| job.schedule.fires_at(minute)) {
+                    let mut sender = ChannelSender::new(&token[..], &job.channel[..], outbox.clone());
+                    // One task panicking shouldn't stop the others, or stop it running next time
+                    if panic::catch_unwind(AssertUnwindSafe(|| job.task.run(&mut sender))).is_err() {
+                        error!("A scheduled task for {} panicked", job.channel);
+                    }
+                }
+            }
+            jobs
+        });
+
+        Scheduler {
+            stop: stop,
+            thread: thread
+        }
+    }
+
+    /// Stop running jobs, waiting for any that are running to finish. The jobs are handed back so
+    /// they can be started again.
+    pub fn stop(self) -> Vec<Job> {
+        let _ = self.stop.send(());
+        self.thread.join().unwrap_or_else(|_| {
+            error!("The scheduler panicked, so scheduled tasks won't run again");
+            Vec::new()
+        })
+    }
+}
+
+fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_secs(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CronSchedule, parse_field, month_and_day};
+
+    // 2024-01-01, a Monday
+    const JAN_1_2024: u64 = 19723;
+
+    fn minute_of(days: u64, hour: u64, minute: u64) -> u64 {
+        (days * 24 + hour) * 60 + minute
+    }
+
+    fn allowed(field: &str, min: usize, max: usize) -> Vec<usize> {
+        let allowed = parse_field(field, min, max).unwrap();
+        (0..allowed.len()).filter(|&value| allowed[value]).collect()
+    }
+
+    #[test]
+    fn parses_fields() {
+        assert_eq!(allowed("*", 1, 5), vec![1, 2, 3, 4, 5]);
+        assert_eq!(allowed("7", 0, 23), vec![7]);
+        assert_eq!(allowed("1-3,15", 1, 31), vec![1, 2, 3, 15]);
+    }
+
+    #[test]
+    fn parses_steps() {
+        assert_eq!(allowed("*/15", 0, 59), vec![0, 15, 30, 45]);
+        assert_eq!(allowed("1-10/3", 1, 31), vec![1, 4, 7, 10]);
+        assert_eq!(allowed("10/20", 0, 59), vec![10, 30, 50]);
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        for field in &["60", "5-1", "*/0", "a", "1-", ""] {
+            assert!(parse_field(field, 0, 59).is_err(), "{} should be invalid", field);
+        }
+        assert!(parse_field("0", 1, 31).is_err());
+    }
+
+    #[test]
+    fn finds_the_month_and_day() {
+        assert_eq!(month_and_day(0), (1, 1));
+        assert_eq!(month_and_day(JAN_1_2024), (1, 1));
+        assert_eq!(month_and_day(JAN_1_2024 + 59), (2, 29));
+        assert_eq!(month_and_day(JAN_1_2024 + 365), (12, 31));
+    }
+
+    #[test]
+    fn fires_at_the_right_minute() {
+        let schedule = CronSchedule::parse("30 9 * * *").unwrap();
+        assert!(schedule.fires_at(minute_of(JAN_1_2024, 9, 30)));
+        assert!(!schedule.fires_at(minute_of(JAN_1_2024, 9, 31)));
+        assert!(!schedule.fires_at(minute_of(JAN_1_2024, 10, 30)));
+    }
+
+    #[test]
+    fn sunday_is_0_or_7() {
+        let sunday = JAN_1_2024 + 6;
+        for expr in &["0 9 * * 0", "0 9 * * 7"] {
+            let schedule = CronSchedule::parse(expr).unwrap();
+            assert!(schedule.fires_at(minute_of(sunday, 9, 0)), "{} should fire on Sunday", expr);
+            assert!(!schedule.fires_at(minute_of(JAN_1_2024, 9, 0)), "{} shouldn't fire on Monday", expr);
+        }
+    }
+
+    #[test]
+    fn fires_on_either_day_field_when_both_are_restricted() {
+        // The 1st of the month, or any Monday
+        let schedule = CronSchedule::parse("0 9 1 * 1").unwrap();
+        assert!(schedule.fires_at(minute_of(JAN_1_2024, 9, 0)));
+        assert!(schedule.fires_at(minute_of(JAN_1_2024 + 7, 9, 0)));
+        assert!(schedule.fires_at(minute_of(JAN_1_2024 + 31, 9, 0)));
+        assert!(!schedule.fires_at(minute_of(JAN_1_2024 + 8, 9, 0)));
+    }
+
+    #[test]
+    fn fires_on_only_the_restricted_day_field() {
+        let first_of_month = CronSchedule::parse("0 9 1 * *").unwrap();
+        assert!(first_of_month.fires_at(minute_of(JAN_1_2024 + 31, 9, 0)));
+        assert!(!first_of_month.fires_at(minute_of(JAN_1_2024 + 7, 9, 0)));
+
+        let weekdays = CronSchedule::parse("0 9 * * 1-5").unwrap();
+        assert!(weekdays.fires_at(minute_of(JAN_1_2024 + 4, 9, 0)));
+        assert!(!weekdays.fires_at(minute_of(JAN_1_2024 + 5, 9, 0)));
+    }
+}
@@ -1,54 +1,1487 @@
-use slack::{RtmClient, User};
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-/// The sender of a command to the bot.
-pub struct Sender<'a> {
-    /// A writable Slack channel that the command came from. Can be used to respond on the same
-    /// channel.
-    channel_writer: ChannelWriter<'a>,
+use slack::{RtmClient, User, Im, Message, Channel, Group};
+use serde_json::{self, Value};
+use futures::{future, Future};
+
+use super::api;
+use super::args::ParsedArgs;
+use super::attachment::Attachment;
+use super::blocks::Block;
+use super::channel_kind::ChannelKind;
+use super::client::{ClientRef, ClientSnapshot};
+use super::error::BotError;
+use super::escape::escape;
+use super::file::SlackFile;
+use super::outbox::{Outbox, Queued};
+use super::icon::Icon;
+use super::identity::BotIdentity;
+use super::presence::{Presence, Presences};
+use super::random::Rng;
+
+/// How long a message can be before it's split up, by default. Slack cuts off messages that are
+/// much longer than this.
+pub const DEFAULT_MAX_RESPONSE_LEN: usize = 4000;
+
+/// The longest name of an entity like `&amp;` or `&#128512;` that messages are split around.
+const MAX_ENTITY_LEN: usize = 8;
+
+/// How often `with_progress` updates its message while the work is going.
+const PROGRESS_UPDATE_SECS: u64 = 5;
+
+/// A message the bot sent, which can be used to update it, react to it or reply to it in a thread.
+#[derive(Clone, Debug)]
+pub struct SentMessage {
+    /// The timestamp Slack gave the message, which identifies it.
+    pub ts: String,
+
+    /// The id of the channel the message was sent to.
+    pub channel: String
+}
+
+/// A message the bot would have sent while replaying events. See `SlackBot::run_with_events`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedMessage {
+    /// The id of the channel the event the bot was handling came from.
+    pub channel: String,
+
+    /// The message, as it would have been sent.
+    pub text: String
+}
+
+/// Everything the bot would have sent while replaying events, in order.
+pub type Recording = Arc<Mutex<Vec<RecordedMessage>>>;
+
+/// Where a sender's messages go.
+enum Connection<'a> {
+    Slack(ClientRef<'a>),
+
+    /// Nowhere, but what would've been sent is kept so tests can check it.
+    Test(Vec<String>),
+
+    /// Nowhere, but what would've been sent is added to a recording shared by every sender the
+    /// bot makes while replaying events.
+    Replay(Recording)
+}
+
+/// Everything about a command that was sent to the bot, like who sent it and where, along with
+/// ways to reply to it. Handlers for other messages and events get one too, about the message or
+/// event they're handling.
+pub struct CommandContext<'a> {
+    connection: Connection<'a>,
+    token: String,
+
+    /// The name of the command, after resolving aliases. This is empty for anything that isn't a
+    /// command.
+    pub command: String,
+
+    /// The command's arguments, which are also given to handlers alongside the context.
+    pub args: Vec<String>,
+
+    /// The command's arguments, split into options and positional arguments.
+    pub parsed_args: ParsedArgs,
 
     /// The user that sent the command.
-    pub user: User
+    pub user: User,
+
+    /// The id of the channel the command came from.
+    pub channel_id: String,
+
+    /// The timestamp of the message the command came from, which Slack uses to identify it.
+    pub ts: String,
+
+    /// The timestamp of the thread the command came from. If the command wasn't sent in a thread,
+    /// this is the timestamp of the command's message, so replying to it starts a new thread.
+    pub thread_ts: String,
+
+    /// The description the command was registered with, if it has one.
+    pub command_description: Option<String>,
+
+    /// The id of the workspace the message came from, if Slack said. Bots in an Enterprise Grid
+    /// can see messages from more than one.
+    pub team_id: Option<String>,
+
+    /// How many bytes a message can be before it's split into several, on line breaks where
+    /// possible. Attachments and blocks aren't split.
+    pub max_response_len: usize,
+
+    /// What the bot has heard about who's around, shared by every context. `user_presence` keeps
+    /// it up to date.
+    pub presences: Presences,
+
+    raw_text: String,
+    files: Vec<SlackFile>,
+    rng: Rng,
+    outbox: Outbox,
+    deregistered: bool,
+    failed: bool
 }
 
-impl<'a> Sender<'a> {
-    pub fn new<S: Into<String>>(client: &'a mut RtmClient, channel_id: S, user: User) -> Self {
-        let channel_writer = ChannelWriter::new(channel_id, client);
-        Sender {
-            channel_writer: channel_writer,
-            user: user
+/// What `CommandContext` was called before it held everything about a command. Handlers for
+/// things other than commands still call it a sender.
+pub type Sender<'a> = CommandContext<'a>;
+
+impl<'a> CommandContext<'a> {
+    pub fn new<A, B, C, D>(client: &'a mut RtmClient, token: A, channel_id: B, ts: C, thread_ts: D, user: User, outbox: Outbox) -> Self
+        where A: Into<String>, B: Into<String>, C: Into<String>, D: Into<String> {
+
+        CommandContext {
+            connection: Connection::Slack(ClientRef::Rtm(client)),
+            token: token.into(),
+            command: String::new(),
+            args: Vec::new(),
+            parsed_args: ParsedArgs::default(),
+            user: user,
+            channel_id: channel_id.into(),
+            ts: ts.into(),
+            thread_ts: thread_ts.into(),
+            command_description: None,
+            team_id: None,
+            max_response_len: DEFAULT_MAX_RESPONSE_LEN,
+            presences: Arc::new(Mutex::new(HashMap::new())),
+            raw_text: String::new(),
+            files: Vec::new(),
+            rng: Rng::from_time(),
+            outbox: outbox,
+            deregistered: false,
+            failed: false
+        }
+    }
+
+    /// Create a context that doesn't talk to Slack, for testing handlers. Messages it's asked to
+    /// send are kept in `responses` instead.
+    ///
+    /// The sender is a user named `tester`, in a channel with the id `C0TEST`. Change its fields
+    /// to test other situations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{CommandContext, CommandHandler};
+    /// let mut echo = |ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel(args.join(" "));
+    /// };
+    ///
+    /// let mut ctx = CommandContext::test();
+    /// echo.handle(&mut ctx, &vec!["Hello,".to_owned(), "world!".to_owned()]);
+    /// assert_eq!(ctx.responses(), &["Hello, world!".to_owned()]);
+    /// ```
+    pub fn test() -> Self {
+        CommandContext {
+            connection: Connection::Test(Vec::new()),
+            token: String::new(),
+            command: String::new(),
+            args: Vec::new(),
+            parsed_args: ParsedArgs::default(),
+            user: User {
+                id: "U0TEST".to_owned(),
+                name: "tester".to_owned(),
+                is_admin: None,
+                is_owner: None,
+                is_primary_owner: None,
+                deleted: false,
+                is_bot: false,
+                tz_offset: None
+            },
+            channel_id: "C0TEST".to_owned(),
+            ts: "1.000000".to_owned(),
+            thread_ts: "1.000000".to_owned(),
+            command_description: None,
+            team_id: None,
+            max_response_len: DEFAULT_MAX_RESPONSE_LEN,
+            presences: Arc::new(Mutex::new(HashMap::new())),
+            raw_text: String::new(),
+            files: Vec::new(),
+            rng: Rng::new(0),
+            outbox: Outbox::new(),
+            deregistered: false,
+            failed: false
+        }
+    }
+
+    /// The full text of the message the command or pattern came from, prefix and all, as it was
+    /// typed. Use this to parse a command's arguments differently, or to keep their spacing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("echo", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     let text = ctx.raw_text().splitn(3, ' ').nth(2).unwrap_or("").to_owned();
+    ///     ctx.respond_in_channel(text);
+    /// }));
+    /// ```
+    pub fn raw_text(&self) -> &str {
+        &self.raw_text[..]
+    }
+
+    /// Set the text that `raw_text` gives back, for testing handlers that use it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let mut ctx = CommandContext::test();
+    /// ctx.set_raw_text("!bot echo   spaced   out");
+    /// assert_eq!(ctx.raw_text(), "!bot echo   spaced   out");
+    /// ```
+    pub fn set_raw_text<S: Into<String>>(&mut self, text: S) {
+        self.raw_text = text.into();
+    }
+
+    /// The files that were shared along with the command's message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("files", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     let names = ctx.attached_files().into_iter().map(|file| file.name).collect::<Vec<_>>();
+    ///     ctx.respond_in_channel(format!("You sent: {}", names.join(", ")));
+    /// }));
+    /// ```
+    pub fn attached_files(&self) -> Vec<SlackFile> {
+        self.files.clone()
+    }
+
+    /// Set the files that `attached_files` gives back, for testing handlers that use them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{CommandContext, SlackFile};
+    /// let mut ctx = CommandContext::test();
+    /// ctx.set_attached_files(vec![SlackFile::new("F12345", "report.csv", "https://files.slack.com/report.csv", "")]);
+    /// assert_eq!(ctx.attached_files()[0].name, "report.csv");
+    /// ```
+    pub fn set_attached_files(&mut self, files: Vec<SlackFile>) {
+        self.files = files;
+    }
+
+    /// Remove the command that's being handled once its handler returns, for commands that should
+    /// only ever run once. This does nothing for handlers of things other than commands.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("setup", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_in_channel("All set up!");
+    ///     ctx.deregister_self();
+    /// }));
+    /// ```
+    pub fn deregister_self(&mut self) {
+        self.deregistered = true;
+    }
+
+    /// Whether the handler asked for its command to be removed with `deregister_self`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let mut ctx = CommandContext::test();
+    /// ctx.deregister_self();
+    /// assert!(ctx.deregistered());
+    /// ```
+    pub fn deregistered(&self) -> bool {
+        self.deregistered
+    }
+
+    /// Mark the command as having failed, for the bot's metrics and lifecycle events. Handlers
+    /// added with `on_try` are marked when they return an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     if args.is_empty() {
+    ///         ctx.respond_in_channel("Deploy what?");
+    ///         ctx.fail();
+    ///     }
+    /// }));
+    /// ```
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// Whether the command failed, because its handler said so with `fail`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let mut ctx = CommandContext::test();
+    /// ctx.fail();
+    /// assert!(ctx.failed());
+    /// ```
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// The messages a test context was asked to send, in order. Contexts that talk to Slack don't
+    /// keep anything.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let mut ctx = CommandContext::test();
+    /// ctx.respond_in_dm("psst");
+    /// assert_eq!(ctx.responses(), &["psst".to_owned()]);
+    /// ```
+    pub fn responses(&self) -> &[String] {
+        match self.connection {
+            Connection::Slack(_) | Connection::Replay(_) => &[],
+            Connection::Test(ref responses) => &responses[..]
+        }
+    }
+
+    /// The client to send a message with. Test and replaying senders record the message instead,
+    /// and don't have a client.
+    fn client_or_record<S: Into<String>>(&mut self, message: S) -> Option<ClientRef<'a>> {
+        match self.connection {
+            Connection::Slack(ref client) => Some(client.clone()),
+            Connection::Test(ref mut responses) => {
+                responses.push(message.into());
+                None
+            },
+            Connection::Replay(ref recording) => {
+                recording.lock().unwrap().push(RecordedMessage {
+                    channel: self.channel_id.clone(),
+                    text: message.into()
+                });
+                None
+            }
+        }
+    }
+
+    /// What a test sender says it sent, with a made up timestamp.
+    fn test_message(&self, channel: &str) -> SentMessage {
+        SentMessage {
+            ts: format!("{}.000000", self.responses().len()),
+            channel: channel.to_owned()
+        }
+    }
+
+    fn writer<S: Into<String>>(&self, channel_id: S) -> ChannelWriter {
+        ChannelWriter::new(channel_id, self.max_response_len, self.outbox.clone())
+    }
+
+    fn client_ref(&self) -> Option<ClientRef<'a>> {
+        match self.connection {
+            Connection::Slack(ref client) => Some(client.clone()),
+            Connection::Test(_) | Connection::Replay(_) => None
+        }
+    }
+
+    /// The slack crate's client that the bot is connected with, for things this crate doesn't do
+    /// yet. Contexts for commands handled on worker threads (see `SlackBot::worker_threads`) and
+    /// test contexts don't have one.
+    ///
+    /// The client is lent to the context for as long as the handler runs, so it can't be kept
+    /// after that or sent to another thread; use an `AsyncSender` or `ChannelSender` for talking
+    /// to Slack later. Only shared access is given, because the bot's event loop lends the same
+    /// client to every context, so methods that need `&mut RtmClient` (like `update_channels`)
+    /// can't be called from a handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("topic", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     if let Some(client) = ctx.client() {
+    ///         let _ = client.set_topic(&ctx.channel_id, &args.join(" "));
+    ///     }
+    /// }));
+    ///
+    /// assert!(CommandContext::test().client().is_none());
+    /// ```
+    pub fn client(&self) -> Option<&'a RtmClient> {
+        match self.connection {
+            Connection::Slack(ClientRef::Rtm(client)) => Some(client),
+            _ => None
+        }
+    }
+
+    /// The name of the channel the command came from, without the leading `#`. Direct messages
+    /// don't have a name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("where", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// if ctx.channel_name() == Some("ops".to_owned()) {
+    ///     ctx.respond_in_channel("You're in ops!");
+    /// }
+    /// # }));
+    /// ```
+    pub fn channel_name(&self) -> Option<String> {
+        let client = match self.client_ref() {
+            Some(client) => client,
+            None => return None
+        };
+        if let Some(channel) = client.get_channels().into_iter().find(|c| c.id == self.channel_id) {
+            return Some(channel.name);
+        }
+        client.get_groups().into_iter().find(|g| g.id == self.channel_id).map(|g| g.name)
+    }
+
+    /// What sort of conversation the message was sent in. Multi-party direct messages are only
+    /// told apart from private channels by the bot's list of groups, so test contexts go by the
+    /// channel id alone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, ChannelKind};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("password", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     if ctx.channel_kind() != ChannelKind::Im {
+    ///         ctx.respond_ephemeral("Ask me that in a direct message.");
+    ///         return;
+    ///     }
+    ///     ctx.respond_in_channel("It's hunter2.");
+    /// }));
+    ///
+    /// let mut ctx = CommandContext::test();
+    /// ctx.channel_id = "D024BE91L".to_owned();
+    /// assert_eq!(ctx.channel_kind(), ChannelKind::Im);
+    /// ```
+    pub fn channel_kind(&self) -> ChannelKind {
+        let group = self.client_ref().and_then(|client| {
+            client.get_groups().into_iter().find(|g| g.id == self.channel_id)
+        });
+        match group {
+            Some(ref group) if group.name.starts_with("mpdm-") => ChannelKind::Mpim,
+            Some(_) => ChannelKind::Private,
+            None => ChannelKind::from_id(&self.channel_id[..])
+        }
+    }
+
+    /// Who the bot is logged in as, and the team it's in. This is only missing if Slack didn't
+    /// send it when the bot logged in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("link", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// if let Some(identity) = ctx.bot_identity() {
+    ///     ctx.respond_in_channel(format!("I'm {} on {}.slack.com", identity.name, identity.team_domain));
+    /// }
+    /// # }));
+    /// ```
+    pub fn bot_identity(&self) -> Option<BotIdentity> {
+        self.client_ref().and_then(|client| client.identity())
+    }
+
+    /// A link to the message the command came from, like
+    /// `https://team.slack.com/archives/C12345/p1234567890123456`. It's built from the team's
+    /// domain when the bot knows it, and asked of Slack otherwise. This is `None` if Slack couldn't
+    /// be asked, and always for test contexts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("bookmark", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     if let Some(link) = ctx.message_permalink() {
+    ///         ctx.respond_in_dm(format!("Bookmarked {}", link));
+    ///     }
+    /// }));
+    ///
+    /// assert_eq!(CommandContext::test().message_permalink(), None);
+    /// ```
+    pub fn message_permalink(&self) -> Option<String> {
+        let client = match self.client_ref() {
+            Some(client) => client,
+            None => return None
+        };
+        if let Some(identity) = client.identity() {
+            return Some(permalink(&identity.team_domain[..], &self.channel_id[..], &self.ts[..], &self.thread_ts[..]));
+        }
+
+        let params = [("channel", &self.channel_id[..]), ("message_ts", &self.ts[..])];
+        match api::call(&self.token[..], "chat.getPermalink", &params) {
+            Ok(data) => data.get("permalink").and_then(Value::as_str).map(str::to_owned),
+            Err(err) => {
+                warn!("Couldn't get a permalink for message {}: {}", self.ts, err);
+                None
+            }
+        }
+    }
+
+    /// Whether a user is around. Slack tells the bot when the users it's asked about come and go,
+    /// so the first lookup for each user asks Slack, and later ones are answered right away. This
+    /// is `None` if Slack couldn't be asked, and always for test contexts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, Presence};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("around", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     let mentioned = ctx.resolve_mentions(args);
+    ///     let around = mentioned.into_iter().filter_map(|user| user)
+    ///         .filter(|user| ctx.user_presence(&user.id) == Some(Presence::Active))
+    ///         .map(|user| user.name)
+    ///         .collect::<Vec<_>>();
+    ///     ctx.respond_in_channel(format!("Around: {}", around.join(", ")));
+    /// }));
+    /// ```
+    pub fn user_presence(&self, user_id: &str) -> Option<Presence> {
+        let client = match self.client_ref() {
+            Some(client) => client,
+            None => return None
+        };
+        if let Some(&presence) = self.presences.lock().unwrap().get(user_id) {
+            return Some(presence);
+        }
+
+        let presence = match api::call(&self.token[..], "users.getPresence", &[("user", user_id)]) {
+            Ok(data) => data.get("presence").and_then(Value::as_str).and_then(Presence::parse),
+            Err(err) => {
+                warn!("Couldn't get the presence of {}: {}", user_id, err);
+                return None;
+            }
+        };
+        if let Some(presence) = presence {
+            let subscribed = {
+                let mut presences = self.presences.lock().unwrap();
+                presences.insert(user_id.to_owned(), presence);
+                presences.keys().cloned().collect::<Vec<_>>()
+            };
+            // Slack only sends presence changes for the users the bot subscribes to
+            let payload = json!({ "type": "presence_sub", "ids": subscribed });
+            if let Err(err) = send_event(client.get_message_sender().as_ref(), &payload) {
+                warn!("Couldn't subscribe to presence changes: {}", err);
+            }
+        }
+        presence
+    }
+
+    /// The channels the bot is in, including private ones. Private channels have `is_channel` set
+    /// to `false`. Test contexts aren't in any channels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("footprint", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     let names = ctx.bot_channels().into_iter().map(|c| c.name).collect::<Vec<_>>();
+    ///     ctx.respond_in_channel(format!("I'm in {} channels: {}", names.len(), names.join(", ")));
+    /// }));
+    /// ```
+    pub fn bot_channels(&self) -> Vec<Channel> {
+        let client = match self.client_ref() {
+            Some(client) => client,
+            None => return Vec::new()
+        };
+        let mut channels = client.get_channels().into_iter().filter(|c| c.is_member).collect::<Vec<_>>();
+        // Slack only tells bots about the private channels they're in
+        channels.extend(client.get_groups().into_iter().map(group_channel));
+        channels
+    }
+
+    /// The users mentioned in some arguments (like `@alice`, which Slack sends as `<@U12345>`),
+    /// with `None` for arguments that aren't mentions or are of users the bot doesn't know.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("assign", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     match ctx.resolve_mentions(&args[..1]).pop() {
+    ///         Some(Some(assignee)) => ctx.respond_in_channel(format!("Assigned to {}", assignee.name)),
+    ///         _ => ctx.respond_in_channel("Who should I assign it to?")
+    ///     };
+    /// }));
+    /// ```
+    pub fn resolve_mentions(&self, args: &[String]) -> Vec<Option<User>> {
+        let users = self.client_ref().map_or(Vec::new(), |client| client.get_users());
+        args.iter().map(|arg| {
+            mentioned_user_id(arg).and_then(|user_id| {
+                if user_id == self.user.id {
+                    Some(self.user.clone())
+                } else {
+                    users.iter().find(|user| user.id == user_id).cloned()
+                }
+            })
+        }).collect()
+    }
+
+    /// Send a message to the channel that the message came from. The message is queued to be sent
+    /// once it's its turn, without waiting, and the handle returned gives back the new message
+    /// once it's been sent, which can be used to update it later.
+    ///
+    /// Characters that Slack would treat as formatting (`&`, `<` and `>`) are escaped, so the
+    /// message shows up as written. To send mentions or links, use `respond_in_channel_raw`.
+    ///
+    /// Messages over `max_response_len` are sent in parts, and the first part is given back once
+    /// they've all been sent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("say-hello", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.respond_in_channel("Hello, world!");
+    /// # }));
+    /// ```
+    pub fn respond_in_channel<S: Into<String>>(&mut self, message: S) -> Queued<SentMessage> {
+        self.respond_in_channel_raw(escape(&message.into()[..]))
+    }
+
+    /// Send a message to the channel that the message came from without escaping it, for messages
+    /// that use Slack's formatting, like `<@U12345>` to mention someone. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("wave", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// let wave = format!("<@{}> waves hello!", ctx.user.id);
+    /// ctx.respond_in_channel_raw(wave);
+    /// # }));
+    /// ```
+    pub fn respond_in_channel_raw<S: Into<String>>(&mut self, message: S) -> Queued<SentMessage> {
+        let message = message.into();
+        match self.client_or_record(&message[..]) {
+            Some(_) => self.writer(&self.channel_id[..]).post(&self.token[..], message),
+            None => Queued::ready(Ok(self.test_message(&self.channel_id[..])))
         }
     }
 
-    /// Send a message to the channel that the message came from.
+    /// Send a message to the channel that the message came from, mentioning the person who sent
+    /// the command so they're notified. The message is escaped like with `respond_in_channel`.
+    /// Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let mut ctx = CommandContext::test();
+    /// ctx.respond_mentioning_user("your build is done & green");
+    /// assert_eq!(ctx.responses(), &["<@U0TEST> your build is done &amp; green".to_owned()]);
+    /// ```
+    pub fn respond_mentioning_user<S: Into<String>>(&mut self, message: S) -> Queued<SentMessage> {
+        let message = format!("<@{}> {}", self.user.id, escape(&message.into()[..]));
+        self.respond_in_channel_raw(message)
+    }
+
+    /// Send a message to the channel that the message came from without any formatting, so that
+    /// things like `*bold*` and `_italic_` show up as typed. Useful for echoing code or what
+    /// people said. Returns the new message.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use slackbot::{SlackBot, Sender};
+    /// # use slackbot::{SlackBot, CommandContext};
     /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
-    /// # my_bot.on("say-hello", Box::new(|sender: &mut Sender, args: &Vec<String>| {
-    /// sender.respond_in_channel("Hello, world!");
+    /// # my_bot.on("echo", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.respond_plain(args.join(" "));
     /// # }));
     /// ```
-    pub fn respond_in_channel<S: Into<String>>(&mut self, message: S) -> Result<(), String> {
-        self.channel_writer.write(message)
+    pub fn respond_plain<S: Into<String>>(&mut self, message: S) -> Queued<SentMessage> {
+        let message = escape(&message.into()[..]);
+        match self.client_or_record(&message[..]) {
+            Some(_) => self.writer(&self.channel_id[..]).post_plain(&self.token[..], message),
+            None => Queued::ready(Ok(self.test_message(&self.channel_id[..])))
+        }
+    }
+
+    /// Send a message to the channel that the message came from under another name and picture,
+    /// so one bot can post as a few different personas. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, Icon};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     ctx.respond_as("deploy-bot", Icon::Emoji("rocket".to_owned()), "Deploying...");
+    /// }));
+    ///
+    /// let mut ctx = CommandContext::test();
+    /// assert!(ctx.respond_as("alert-bot", Icon::Url("not a url".to_owned()), "Uh oh").wait().is_err());
+    /// ```
+    pub fn respond_as(&mut self, username: &str, icon: Icon, message: &str) -> Queued<SentMessage> {
+        if username.trim().is_empty() {
+            return Queued::ready(Err(BotError::Command("Messages need a name to be posted under".to_owned())));
+        }
+        let icon = match icon.param() {
+            Ok(icon) => icon,
+            Err(err) => return Queued::ready(Err(err))
+        };
+        let message = escape(message);
+        match self.client_or_record(&message[..]) {
+            Some(_) => self.writer(&self.channel_id[..]).post_as(&self.token[..], username, icon, message),
+            None => Queued::ready(Ok(self.test_message(&self.channel_id[..])))
+        }
+    }
+
+    /// Send one of `choices` to the channel that the message came from, picked at random. Fails
+    /// without sending anything when there's nothing to choose from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("8ball", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.respond_random(&["It is certain", "Ask again later", "Very doubtful"]);
+    /// # }));
+    /// ```
+    pub fn respond_random(&mut self, choices: &[&str]) -> Queued<SentMessage> {
+        if choices.is_empty() {
+            return Queued::ready(Err(BotError::Command("There's nothing to choose a response from".to_owned())));
+        }
+
+        let choice = choices[self.rng.below(choices.len())];
+        self.respond_in_channel(choice)
+    }
+
+    /// Seed the random numbers `respond_random` uses, so the same seed picks the same responses.
+    /// Test contexts always start with the same seed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let choices = ["heads", "tails"];
+    ///
+    /// let mut first = CommandContext::test();
+    /// first.seed_random(42);
+    /// first.respond_random(&choices).wait().unwrap();
+    ///
+    /// let mut second = CommandContext::test();
+    /// second.seed_random(42);
+    /// second.respond_random(&choices).wait().unwrap();
+    ///
+    /// assert_eq!(first.responses(), second.responses());
+    /// assert!(CommandContext::test().respond_random(&[]).wait().is_err());
+    /// ```
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// Send a message to another channel, given by name (with or without the `#`) or by id. The
+    /// bot has to be a member of the channel. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("announce", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// let announcement = args.join(" ");
+    /// for channel in &["#general", "#random"] {
+    ///     ctx.respond_to_channel(channel, &announcement[..]);
+    /// }
+    /// # }));
+    /// ```
+    pub fn respond_to_channel(&mut self, channel_id_or_name: &str, message: &str) -> Queued<SentMessage> {
+        let message = escape(message);
+        let client = match self.client_or_record(&message[..]) {
+            Some(client) => client,
+            None => return Queued::ready(Ok(self.test_message(channel_id_or_name)))
+        };
+
+        let wanted = channel_id_or_name.trim_left_matches('#');
+        let channel_id = match client.get_channels().into_iter().find(|c| c.id == wanted || c.name == wanted) {
+            Some(ref channel) if !channel.is_member => {
+                return Queued::ready(Err(BotError::Slack(format!("Not a member of #{}", channel.name))));
+            },
+            Some(channel) => channel.id,
+            // Private channels are only listed if the bot is in them
+            None => match client.get_groups().into_iter().find(|g| g.id == wanted || g.name == wanted) {
+                Some(group) => group.id,
+                None => return Queued::ready(Err(BotError::Slack(format!("Couldn't find channel `{}`", channel_id_or_name))))
+            }
+        };
+        self.writer(channel_id).post(&self.token[..], &message[..])
+    }
+
+    /// Change the text of a message the bot sent to the channel that the message came from, given
+    /// the timestamp it was sent with. The change waits its turn with the channel's messages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// let sent = ctx.respond_in_channel("Deploying...").wait().unwrap();
+    /// // ...deploy...
+    /// ctx.update_message(&sent.ts, "Deploying... done!");
+    /// # }));
+    /// ```
+    pub fn update_message(&mut self, ts: &str, new_text: &str) -> Queued<()> {
+        let new_text = escape(new_text);
+        if self.client_or_record(&new_text[..]).is_none() {
+            return Queued::ready(Ok(()));
+        }
+        let params = [("channel", &self.channel_id[..]), ("ts", ts), ("text", &new_text[..]), ("as_user", "true")];
+        let call = api::Call::new(&self.token[..], "chat.update", &params);
+        self.outbox.send(&self.channel_id[..], move || call.send().map(|_| ()))
+    }
+
+    /// Do some slow work while showing that the bot is on it. `message` is sent to the channel
+    /// that the message came from, and updated with how long it's been every few seconds until
+    /// `work` is done. Then it's changed to what `work` returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::CommandContext;
+    /// let mut ctx = CommandContext::test();
+    /// ctx.with_progress("Crunching the numbers...", || {
+    ///     // ...crunch the numbers...
+    ///     "The answer is 42"
+    /// }).unwrap();
+    /// assert_eq!(ctx.responses(), &["Crunching the numbers...".to_owned(), "The answer is 42".to_owned()]);
+    /// ```
+    pub fn with_progress<F, S>(&mut self, message: &str, work: F) -> Result<SentMessage, BotError>
+        where F: FnOnce() -> S, S: Into<String> {
+
+        let sent = try!(self.respond_in_channel(message).wait());
+        let ticker = self.client_ref().map(|_| {
+            let (stop, stopped) = mpsc::channel::<()>();
+            let (token, channel, ts, message) = (self.token.clone(), self.channel_id.clone(), sent.ts.clone(), escape(message));
+            let outbox = self.outbox.clone();
+            let ticker = thread::spawn(move || {
+                let started = Instant::now();
+                // Nothing is ever sent, so this times out until the sending half is dropped
+                while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(Duration::from_secs(PROGRESS_UPDATE_SECS)) {
+                    let text = format!("{} ({}s)", message, started.elapsed().as_secs());
+                    let params = [("channel", &channel[..]), ("ts", &ts[..]), ("text", &text[..]), ("as_user", "true")];
+                    // Updates wait their turn with the channel's other messages, and each one is
+                    // waited for so they can't pile up
+                    let call = api::Call::new(&token[..], "chat.update", &params);
+                    if let Err(err) = outbox.send(&channel[..], move || call.send()).wait() {
+                        warn!("Couldn't update progress message: {}", err);
+                    }
+                }
+            });
+            (stop, ticker)
+        });
+
+        let result = work().into();
+        if let Some((stop, ticker)) = ticker {
+            drop(stop);
+            let _ = ticker.join();
+        }
+        try!(self.update_message(&sent.ts[..], &result[..]).wait());
+        Ok(sent)
+    }
+
+    /// Send a message to the thread that the message came from, starting a new thread if it wasn't
+    /// already in one. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("say-hello", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.respond_in_thread("Hello, thread!");
+    /// # }));
+    /// ```
+    pub fn respond_in_thread<S: Into<String>>(&mut self, message: S) -> Queued<SentMessage> {
+        let message = escape(&message.into()[..]);
+        match self.client_or_record(&message[..]) {
+            Some(_) => self.writer(&self.channel_id[..]).post_in_thread(&self.token[..], message, &self.thread_ts[..]),
+            None => Queued::ready(Ok(self.test_message(&self.channel_id[..])))
+        }
+    }
+
+    /// Send a message to the channel that the message came from that only the user that sent the
+    /// command can see. If Slack won't post it there, it's sent to them as a direct message
+    /// instead. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// if args.is_empty() {
+    ///     ctx.respond_ephemeral("Usage: deploy <environment>");
+    /// }
+    /// # }));
+    /// ```
+    pub fn respond_ephemeral<S: Into<String>>(&mut self, message: S) -> Queued<SentMessage> {
+        let message = escape(&message.into()[..]);
+        let client = match self.client_or_record(&message[..]) {
+            Some(client) => client,
+            None => return Queued::ready(Ok(self.test_message(&self.channel_id[..])))
+        };
+        let params = [
+            ("channel", &self.channel_id[..]),
+            ("user", &self.user.id[..]),
+            ("text", &message[..]),
+            ("as_user", "true")
+        ];
+        let call = api::Call::new(&self.token[..], "chat.postEphemeral", &params);
+        let (writer, token, channel_id, user_id) = (self.writer(""), self.token.clone(), self.channel_id.clone(), self.user.id.clone());
+        let im_id = find_im_channel(&client.get_start_ims().unwrap_or(vec![]), &user_id[..]);
+        let sent = self.outbox.send(&self.channel_id[..], move || match call.send() {
+            Ok(data) => match data.get("message_ts") {
+                Some(&Value::String(ref ts)) => Ok(Queued::ready(Ok(SentMessage {
+                    ts: ts.to_owned(),
+                    channel: channel_id.clone()
+                }))),
+                _ => Err(BotError::Slack("chat.postEphemeral didn't return a timestamp".to_owned()))
+            },
+            // Errors that might go away are tried again by the outbox instead
+            Err(err) => match err {
+                BotError::Api { ref error, .. } if !err.is_transient() => {
+                    debug!("Couldn't send ephemeral message ({}), sending a direct message instead", error);
+                    Ok(post_dm(writer.clone(), &token[..], &user_id[..], im_id.clone(), message.clone()))
+                },
+                err => Err(err)
+            }
+        });
+        Queued::new(sent.flatten())
+    }
+
+    /// Show that the bot is typing in the channel that the message came from, until it next sends
+    /// a message there. This is useful before doing something slow, so people know the bot is
+    /// working on it.
+    ///
+    /// If the bot can't reach Slack right now, this does nothing rather than failing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("think", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.send_typing();
+    /// // ...think really hard...
+    /// ctx.respond_in_channel("42");
+    /// # }));
+    /// ```
+    pub fn send_typing(&mut self) -> Result<(), BotError> {
+        if let Some(client) = self.client_ref() {
+            let payload = json!({
+                "id": client.get_msg_uid(),
+                "type": "typing",
+                "channel": self.channel_id
+            });
+            if let Err(err) = send_event(client.get_message_sender().as_ref(), &payload) {
+                debug!("Couldn't send typing indicator: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Send an attachment to the channel that the message came from. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, Attachment};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("status", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.respond_with_attachment(Attachment::new().color("good").text("All systems go!"));
+    /// # }));
+    /// ```
+    pub fn respond_with_attachment(&mut self, attachment: Attachment) -> Queued<SentMessage> {
+        let attachments = match serde_json::to_string(&[attachment]) {
+            Ok(attachments) => attachments,
+            Err(err) => return Queued::ready(Err(BotError::from(err)))
+        };
+        match self.client_or_record(&attachments[..]) {
+            Some(_) => self.writer(&self.channel_id[..]).write_attachments(&self.token[..], &attachments[..]),
+            None => Queued::ready(Ok(self.test_message(&self.channel_id[..])))
+        }
+    }
+
+    /// Send a Block Kit layout to the channel that the message came from. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext, Block, Actions, Button};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.respond_with_blocks(vec![
+    ///     Block::section("Ready to deploy to *production*?"),
+    ///     Block::Actions(Actions::new()
+    ///         .button(Button::new("Deploy", "deploy").style("primary"))
+    ///         .button(Button::new("Cancel", "cancel")))
+    /// ]);
+    /// # }));
+    /// ```
+    pub fn respond_with_blocks(&mut self, blocks: Vec<Block>) -> Queued<SentMessage> {
+        let blocks = match serde_json::to_string(&blocks) {
+            Ok(blocks) => blocks,
+            Err(err) => return Queued::ready(Err(BotError::from(err)))
+        };
+        match self.client_or_record(&blocks[..]) {
+            Some(_) => self.writer(&self.channel_id[..]).write_blocks(&self.token[..], &blocks[..]),
+            None => Queued::ready(Ok(self.test_message(&self.channel_id[..])))
+        }
+    }
+
+    /// Upload a snippet to the channel that the message came from, for output that's too long
+    /// for a message. The file type (e.g. `csv` or `rust`) sets the snippet's syntax highlighting,
+    /// and Slack guesses it from the file name if it isn't given. Gives back the id of the uploaded
+    /// file once it's been uploaded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("logs", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// let logs = "12:00 started\n12:01 still going\n12:02 done";
+    /// ctx.upload_snippet("build.log", logs, Some("text"));
+    /// # }));
+    /// ```
+    pub fn upload_snippet(&mut self, filename: &str, content: &str, filetype: Option<&str>) -> Queued<String> {
+        if self.client_or_record(content).is_none() {
+            return Queued::ready(Ok(format!("F{}", self.responses().len())));
+        }
+        let (token, channel_id, filename, content) = (self.token.clone(), self.channel_id.clone(), filename.to_owned(), content.to_owned());
+        let filetype = filetype.map(str::to_owned);
+        self.outbox.send(&self.channel_id[..], move || {
+            let mut params = vec![("channels", &channel_id[..]), ("filename", &filename[..])];
+            if let Some(ref filetype) = filetype {
+                params.push(("filetype", &filetype[..]));
+            }
+
+            let data = try!(api::upload(&token[..], "files.upload", &params, &filename[..], &content[..]));
+            match data.pointer("/file/id") {
+                Some(&Value::String(ref id)) => Ok(id.to_owned()),
+                _ => Err(BotError::Slack("files.upload didn't return a file id".to_owned()))
+            }
+        })
+    }
+
+    /// React to the command's message with an emoji, given by name with or without colons (e.g.
+    /// `eyes` or `:eyes:`). Reacting with an emoji that's already there isn't an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("deploy", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.react("eyes");
+    /// // ...deploy...
+    /// ctx.react("white_check_mark");
+    /// # }));
+    /// ```
+    pub fn react(&mut self, emoji: &str) -> Queued<()> {
+        if self.client_ref().is_none() {
+            return Queued::ready(Ok(()));
+        }
+        let (token, emoji, channel_id, ts) = (self.token.clone(), emoji.to_owned(), self.channel_id.clone(), self.ts.clone());
+        self.outbox.send(&self.channel_id[..], move || api::add_reaction(&token[..], &emoji[..], &channel_id[..], &ts[..]))
+    }
+
+    /// Send a message directly to the user that sent the command, opening a direct message channel
+    /// with them if there isn't one already. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// # my_bot.on("whisper", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    /// ctx.respond_in_dm("Just between you and me...");
+    /// # }));
+    /// ```
+    pub fn respond_in_dm<S: Into<String>>(&mut self, message: S) -> Queued<SentMessage> {
+        self.send_dm(escape(&message.into()[..]))
+    }
+
+    /// Send a message directly to another user, given by name (with or without the `@`), by id, or
+    /// by a mention of them. Fails if the bot doesn't know of the user. Returns the new message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use slackbot::{SlackBot, CommandContext};
+    /// # let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+    /// my_bot.on("page", Box::new(|ctx: &mut CommandContext, args: &Vec<String>| {
+    ///     let message = format!("{} needs you in <#{}>", ctx.user.name, ctx.channel_id);
+    ///     if let Err(err) = ctx.dm_user(&args[0], &message).wait() {
+    ///         ctx.respond_in_channel(format!("Couldn't page them: {}", err));
+    ///     }
+    /// }));
+    /// ```
+    pub fn dm_user(&mut self, user_name_or_id: &str, message: &str) -> Queued<SentMessage> {
+        let message = escape(message);
+        let client = match self.client_or_record(&message[..]) {
+            Some(client) => client,
+            None => return Queued::ready(Ok(self.test_message(user_name_or_id)))
+        };
+
+        let wanted = mentioned_user_id(user_name_or_id).unwrap_or(user_name_or_id.trim_left_matches('@'));
+        let user_id = match client.get_users().into_iter().find(|u| u.id == wanted || u.name == wanted) {
+            Some(user) => user.id,
+            None => return Queued::ready(Err(BotError::Slack(format!("Couldn't find user `{}`", user_name_or_id))))
+        };
+        self.write_dm(client, &user_id[..], message)
+    }
+
+    fn send_dm(&mut self, message: String) -> Queued<SentMessage> {
+        let client = match self.client_or_record(&message[..]) {
+            Some(client) => client,
+            None => return Queued::ready(Ok(self.test_message(&self.channel_id[..])))
+        };
+        self.write_dm(client, &self.user.id[..], message)
+    }
+
+    fn write_dm(&self, client: ClientRef<'a>, user_id: &str, message: String) -> Queued<SentMessage> {
+        let im_id = find_im_channel(&client.get_start_ims().unwrap_or(vec![]), user_id);
+        post_dm(self.writer(""), &self.token[..], user_id, im_id, message)
     }
 }
 
-struct ChannelWriter<'a> {
+/// Move a context off the bot's event loop, to a thread that can't borrow the RTM client. It
+/// reads from a snapshot of what the client knows right now instead, and still sends over the
+/// same connection.
+pub fn detach<F>(ctx: CommandContext, snapshot: F) -> CommandContext<'static>
+    where F: FnOnce(&RtmClient) -> Arc<ClientSnapshot> {
+
+    let connection = match ctx.connection {
+        Connection::Slack(ClientRef::Rtm(client)) => Connection::Slack(ClientRef::Snapshot(snapshot(client))),
+        Connection::Slack(ClientRef::Snapshot(snapshot)) => Connection::Slack(ClientRef::Snapshot(snapshot)),
+        Connection::Test(responses) => Connection::Test(responses),
+        Connection::Replay(recording) => Connection::Replay(recording)
+    };
+    CommandContext {
+        connection: connection,
+        token: ctx.token,
+        command: ctx.command,
+        args: ctx.args,
+        parsed_args: ctx.parsed_args,
+        user: ctx.user,
+        channel_id: ctx.channel_id,
+        ts: ctx.ts,
+        thread_ts: ctx.thread_ts,
+        command_description: ctx.command_description,
+        team_id: ctx.team_id,
+        max_response_len: ctx.max_response_len,
+        presences: ctx.presences,
+        raw_text: ctx.raw_text,
+        files: ctx.files,
+        rng: ctx.rng,
+        outbox: ctx.outbox,
+        deregistered: ctx.deregistered,
+        failed: ctx.failed
+    }
+}
+
+/// Have a context record what it sends instead of sending it, for replaying events.
+pub fn record(mut ctx: CommandContext, recording: Recording) -> CommandContext {
+    ctx.connection = Connection::Replay(recording);
+    ctx
+}
+
+/// The link Slack uses for a message. Replies in threads link to the thread too, so they open
+/// in it.
+fn permalink(team_domain: &str, channel_id: &str, ts: &str, thread_ts: &str) -> String {
+    let link = format!("https://{}.slack.com/archives/{}/p{}", team_domain, channel_id, ts.replace(".", ""));
+    if thread_ts == ts {
+        link
+    } else {
+        format!("{}?thread_ts={}&cid={}", link, thread_ts, channel_id)
+    }
+}
+
+/// A private channel as a `Channel`, so it can be listed with public ones.
+fn group_channel(group: Group) -> Channel {
+    Channel {
+        id: group.id,
+        name: group.name,
+        is_channel: false,
+        created: group.created,
+        creator: group.creator,
+        is_archived: group.is_archived,
+        is_general: false,
+        members: group.members,
+        topic: group.topic,
+        purpose: group.purpose,
+        is_member: true,
+        last_read: group.last_read,
+        unread_count: group.unread_count,
+        unread_count_display: group.unread_count_display
+    }
+}
+
+/// The id of the user an argument mentions, if it's a mention like `<@U12345>` or
+/// `<@U12345|alice>`.
+fn mentioned_user_id(arg: &str) -> Option<&str> {
+    if !arg.starts_with("<@") || !arg.ends_with('>') {
+        return None;
+    }
+    let mention = &arg[2..arg.len() - 1];
+    match mention.find('|') {
+        Some(end) => Some(&mention[..end]),
+        None => Some(mention)
+    }
+}
+
+fn find_im_channel(ims: &[Im], user_id: &str) -> Option<String> {
+    ims.iter().find(|im| im.user == user_id).map(|im| im.id.clone())
+}
+
+/// Send a message to a user's direct message channel, given its id if it's already open. If it
+/// isn't, it's opened through the outbox first, and the outbox queues the message once it's open.
+fn post_dm(mut writer: ChannelWriter, token: &str, user_id: &str, im_id: Option<String>, message: String) -> Queued<SentMessage> {
+    if let Some(im_id) = im_id {
+        writer.channel_id = im_id;
+        return writer.post(token, message);
+    }
+
+    let (token, opening) = (token.to_owned(), user_id.to_owned());
+    let outbox = writer.outbox.clone();
+    let opened = outbox.send(user_id, move || {
+        writer.channel_id = try!(api::open_im(&token[..], &opening[..]));
+        Ok(writer.post(&token[..], message.clone()))
+    });
+    Queued::new(opened.flatten())
+}
+
+/// Sends messages to a channel through the outbox. It doesn't need the client, so it can be kept
+/// for sending to a channel that's only known later, like a direct message that's being opened.
+#[derive(Clone)]
+struct ChannelWriter {
     channel_id: String,
-    client: &'a RtmClient
+    max_len: usize,
+    outbox: Outbox
 }
 
-impl<'a> ChannelWriter<'a> {
-    fn new<S: Into<String>>(channel_id: S, client: &'a RtmClient) -> Self {
+impl ChannelWriter {
+    fn new<S: Into<String>>(channel_id: S, max_len: usize, outbox: Outbox) -> Self {
         ChannelWriter {
             channel_id: channel_id.into(),
-            client: client
+            max_len: max_len,
+            outbox: outbox
         }
     }
 
-    fn write<S: Into<String>>(&mut self, message: S) -> Result<(), String> {
-        self.client.send_message(&self.channel_id[..], &message.into()[..])
+    // RTM doesn't tell us the timestamp of messages we send, so these go through the web API
+    fn post<S: Into<String>>(&mut self, token: &str, message: S) -> Queued<SentMessage> {
+        self.post_with(token, message, "true")
+    }
+
+    // Turning formatting off isn't something RTM messages can do either
+    fn post_plain<S: Into<String>>(&mut self, token: &str, message: S) -> Queued<SentMessage> {
+        self.post_with(token, message, "false")
+    }
+
+    fn post_with<S: Into<String>>(&mut self, token: &str, message: S, mrkdwn: &str) -> Queued<SentMessage> {
+        let message = message.into();
+        let parts = split_message(&message[..], self.max_len);
+        first_sent(parts.into_iter().map(|part| self.post_text(token, part, mrkdwn)).collect())
+    }
+
+    fn post_text(&self, token: &str, text: &str, mrkdwn: &str) -> Queued<SentMessage> {
+        let params = [("channel", &self.channel_id[..]), ("text", text), ("mrkdwn", mrkdwn), ("as_user", "true")];
+        self.post_message(token, &params)
+    }
+
+    // Neither can other names, which also need the message to not be sent as the bot's user
+    fn post_as(&mut self, token: &str, username: &str, icon: (&str, String), message: String) -> Queued<SentMessage> {
+        let parts = split_message(&message[..], self.max_len);
+        first_sent(parts.into_iter().map(|part| self.post_text_as(token, part, username, &icon)).collect())
+    }
+
+    fn post_text_as(&self, token: &str, text: &str, username: &str, icon: &(&str, String)) -> Queued<SentMessage> {
+        let params = [("channel", &self.channel_id[..]), ("text", text), ("username", username),
+                      (icon.0, &icon.1[..]), ("as_user", "false")];
+        self.post_message(token, &params)
+    }
+
+    // Blocks can't be sent over RTM either
+    fn write_blocks(&mut self, token: &str, blocks: &str) -> Queued<SentMessage> {
+        let params = [("channel", &self.channel_id[..]), ("blocks", blocks), ("as_user", "true")];
+        self.post_message(token, &params)
+    }
+
+    fn post_message(&self, token: &str, params: &[(&str, &str)]) -> Queued<SentMessage> {
+        let channel_id = self.channel_id.clone();
+        let call = api::Call::new(token, "chat.postMessage", params);
+        self.outbox.send(&self.channel_id[..], move || {
+            let data = try!(call.send());
+            match data.get("ts") {
+                Some(&Value::String(ref ts)) => Ok(SentMessage {
+                    ts: ts.to_owned(),
+                    channel: channel_id.clone()
+                }),
+                _ => Err(BotError::Slack("chat.postMessage didn't return a timestamp".to_owned()))
+            }
+        })
+    }
+
+    // Attachments can't be sent over RTM, so they go through the web API instead
+    fn write_attachments(&mut self, token: &str, attachments: &str) -> Queued<SentMessage> {
+        let params = [("channel", &self.channel_id[..]), ("attachments", attachments), ("as_user", "true")];
+        self.post_message(token, &params)
+    }
+
+    // Messages in threads are posted too, so they can be replied to and updated like the others
+    fn post_in_thread(&mut self, token: &str, message: String, thread_ts: &str) -> Queued<SentMessage> {
+        let parts = split_message(&message[..], self.max_len);
+        first_sent(parts.into_iter().map(|part| self.post_text_in_thread(token, part, thread_ts)).collect())
+    }
+
+    fn post_text_in_thread(&self, token: &str, text: &str, thread_ts: &str) -> Queued<SentMessage> {
+        let params = [("channel", &self.channel_id[..]), ("text", text), ("thread_ts", thread_ts), ("as_user", "true")];
+        self.post_message(token, &params)
+    }
+}
+
+/// The first part of a message that was sent in parts, once every part has been sent. If any
+/// part couldn't be sent, that's the error.
+fn first_sent(parts: Vec<Queued<SentMessage>>) -> Queued<SentMessage> {
+    Queued::new(future::join_all(parts).map(|mut sent| sent.remove(0)))
+}
+
+/// Split a message into parts no longer than `max_len` bytes, breaking at the last line break or
+/// space in each part if there is one. Entities like `&amp;` and tokens like `<@U12345>` aren't
+/// broken up, unless one's too long for a part on its own. There's always at least one part.
+fn split_message(message: &str, max_len: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut rest = message;
+    while rest.len() > max_len {
+        let mut end = max_len;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            // A single character that's too long on its own still has to go somewhere
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        match span_start(rest, end) {
+            Some(start) if start > 0 => end = start,
+            _ => {}
+        }
+
+        let safe_split = |separator: char| {
+            rest[..end].rmatch_indices(separator)
+                .map(|(split, _)| split)
+                .find(|&split| split > 0 && span_start(rest, split).is_none())
+        };
+        match safe_split('\n').or_else(|| safe_split(' ')) {
+            Some(split) => {
+                parts.push(&rest[..split]);
+                rest = &rest[split + 1..];
+            },
+            _ => {
+                parts.push(&rest[..end]);
+                rest = &rest[end..];
+            }
+        }
+    }
+    if !rest.is_empty() || parts.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+/// Where the entity or `<...>` token that splitting `text` at `pos` would break up starts, if
+/// splitting there would break one up.
+fn span_start(text: &str, pos: usize) -> Option<usize> {
+    if let Some(open) = text[..pos].rfind('<') {
+        if !text[open..pos].contains('>') && text[pos..].contains('>') {
+            return Some(open);
+        }
+    }
+    if let Some(amp) = text[..pos].rfind('&') {
+        let entity = text[amp + 1..].find(';').map(|len| &text[amp + 1..amp + 1 + len]);
+        if let Some(name) = entity {
+            let is_entity = name.len() <= MAX_ENTITY_LEN && name.chars().all(|c| c.is_alphanumeric() || c == '#');
+            if is_entity && amp + 1 + name.len() >= pos {
+                return Some(amp);
+            }
+        }
+    }
+    None
+}
+
+/// Send an event over the RTM connection, given the sending half of the client's message channel.
+pub fn send_event(tx: Option<&mpsc::Sender<Message>>, payload: &Value) -> Result<(), BotError> {
+    let json = try!(serde_json::to_string(payload));
+
+    match tx {
+        Some(tx) => tx.send(Message::Text(json)).map_err(|err| BotError::Network(format!("{:?}", err))),
+        None => Err(BotError::Network("Not connected to Slack".to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slack::Im;
+
+    use super::{find_im_channel, split_message};
+
+    fn im(id: &str, user: &str) -> Im {
+        Im {
+            id: id.to_owned(),
+            is_im: true,
+            user: user.to_owned(),
+            created: 1360782804,
+            is_user_deleted: None
+        }
+    }
+
+    #[test]
+    fn finds_the_im_channel_for_a_user() {
+        let ims = vec![im("D024BFF1M", "USLACKBOT"), im("D024BE7RE", "U024BE7LH")];
+        assert_eq!(find_im_channel(&ims, "U024BE7LH"), Some("D024BE7RE".to_owned()));
+    }
+
+    #[test]
+    fn finds_no_im_channel_for_a_user_without_one() {
+        let ims = vec![im("D024BFF1M", "USLACKBOT")];
+        assert_eq!(find_im_channel(&ims, "U024BE7LH"), None);
+    }
+
+    #[test]
+    fn splits_messages_around_entities() {
+        assert_eq!(split_message("abcd&amp;efgh", 6), vec!["abcd", "&amp;e", "fgh"]);
+        assert_eq!(split_message("abc&lt;&gt;", 5), vec!["abc", "&lt;", "&gt;"]);
+    }
+
+    #[test]
+    fn splits_messages_around_mentions() {
+        assert_eq!(split_message("abcdefgh<@U024BE7LH>", 12), vec!["abcdefgh", "<@U024BE7LH>"]);
+    }
+
+    #[test]
+    fn splits_messages_at_spaces_outside_links() {
+        assert_eq!(split_message("see <https://ex.io|the docs>", 24), vec!["see", "<https://ex.io|the docs>"]);
+        assert_eq!(split_message("in <#C024BE7LR|general> now", 24), vec!["in <#C024BE7LR|general>", "now"]);
+    }
+
+    #[test]
+    fn splits_plain_messages_at_spaces() {
+        assert_eq!(split_message("hello there world", 11), vec!["hello", "there world"]);
+        assert_eq!(split_message("short", 11), vec!["short"]);
     }
 }
@@ -0,0 +1,166 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc;
+
+use slack::Message;
+
+/// What the running bot and its shutdown handles share.
+pub struct ShutdownState {
+    stopping: bool,
+    tx: Option<mpsc::Sender<Message>>
+}
+
+impl ShutdownState {
+    pub fn new() -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(ShutdownState {
+            stopping: false,
+            tx: None
+        }))
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stopping
+    }
+
+    /// Called once the bot has connected, so that stopping can close the connection.
+    pub fn connected(&mut self, tx: Option<mpsc::Sender<Message>>) {
+        self.tx = tx;
+        if self.stopping {
+            self.close();
+        }
+    }
+
+    /// Called once the bot has stopped running, so that it can be run again.
+    pub fn reset(&mut self) {
+        self.stopping = false;
+        self.tx = None;
+    }
+
+    fn close(&mut self) {
+        if let Some(ref tx) = self.tx {
+            let _ = tx.send(Message::Close(None));
+        }
+    }
+}
+
+/// The commands that are still being handled off the event loop, on worker threads or as async
+/// commands, so the bot can wait for them before it stops.
+#[derive(Clone)]
+pub struct InFlight {
+    shared: Arc<(Mutex<usize>, Condvar)>
+}
+
+/// One command being handled off the event loop. It's finished when this is dropped.
+pub struct Running {
+    shared: Arc<(Mutex<usize>, Condvar)>
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        InFlight {
+            shared: Arc::new((Mutex::new(0), Condvar::new()))
+        }
+    }
+
+    /// Start handling a command.
+    pub fn start(&self) -> Running {
+        *self.shared.0.lock().unwrap() += 1;
+        Running {
+            shared: self.shared.clone()
+        }
+    }
+
+    /// Wait until every command that's been started has finished.
+    pub fn wait(&self) {
+        let &(ref running, ref finished) = &*self.shared;
+        let mut running = running.lock().unwrap();
+        if *running > 0 {
+            info!("Waiting for {} commands to finish", *running);
+        }
+        while *running > 0 {
+            running = finished.wait(running).unwrap();
+        }
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let &(ref running, ref finished) = &*self.shared;
+        *running.lock().unwrap() -= 1;
+        finished.notify_all();
+    }
+}
+
+/// A handle that can stop a running bot, even from another thread.
+///
+/// Stopping closes the bot's connection to Slack, which makes `SlackBot::run` return. No new
+/// commands are handled after stopping, and the ones that are being handled at the time finish
+/// first, including any on worker threads and async commands, before the bot's state is saved
+/// and `run` returns.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// # use slackbot::SlackBot;
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let mut my_bot = SlackBot::new("bot", "YOUR_API_TOKEN");
+/// let handle = my_bot.shutdown_handle();
+///
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(60));
+///     handle.stop();
+/// });
+///
+/// my_bot.run().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    state: Arc<Mutex<ShutdownState>>
+}
+
+impl ShutdownHandle {
+    pub fn new(state: Arc<Mutex<ShutdownState>>) -> Self {
+        ShutdownHandle {
+            state: state
+        }
+    }
+
+    /// Stop the bot. If it hasn't connected yet, it stops as soon as it does.
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.stopping = true;
+        state.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+    use std::sync::{Arc, Mutex};
+
+    use super::InFlight;
+
+    #[test]
+    fn waits_for_running_commands_to_finish() {
+        let in_flight = InFlight::new();
+        let finished = Arc::new(Mutex::new(false));
+        let (running, done) = (in_flight.start(), finished.clone());
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            *done.lock().unwrap() = true;
+            drop(running);
+        });
+
+        in_flight.wait();
+        assert!(*finished.lock().unwrap());
+    }
+
+    #[test]
+    fn doesnt_wait_with_nothing_running() {
+        let in_flight = InFlight::new();
+        drop(in_flight.start());
+        in_flight.wait();
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Map, Value};
+
+use super::error::BotError;
+
+/// A trait implemented by types that keep a bot's state somewhere that outlasts the bot, like a
+/// file or a database.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate slackbot;
+/// # fn main() {
+/// # use std::cell::RefCell;
+/// # use std::collections::HashMap;
+/// use serde_json::Value;
+/// use slackbot::{StateStore, BotError};
+///
+/// struct InMemory(RefCell<HashMap<String, Value>>);
+///
+/// impl StateStore for InMemory {
+///     fn save(&self, key: &str, value: &Value) -> Result<(), BotError> {
+///         self.0.borrow_mut().insert(key.to_owned(), value.clone());
+///         Ok(())
+///     }
+///
+///     fn load(&self, key: &str) -> Result<Option<Value>, BotError> {
+///         Ok(self.0.borrow().get(key).cloned())
+///     }
+/// }
+/// # }
+/// ```
+pub trait StateStore {
+    /// Save a value under a key, replacing whatever was there.
+    fn save(&self, key: &str, value: &Value) -> Result<(), BotError>;
+
+    /// Load the value saved under a key, if there is one.
+    fn load(&self, key: &str) -> Result<Option<Value>, BotError>;
+}
+
+/// A `StateStore` that keeps everything in one JSON file, as an object with a field for each key.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # extern crate slackbot;
+/// # fn main() {
+/// # use std::env;
+/// use slackbot::{StateStore, JsonFileStore};
+///
+/// let path = env::temp_dir().join("slackbot-doc-state.json");
+/// let store = JsonFileStore::new(&path);
+/// store.save("scores", &json!({"alice": 3})).unwrap();
+/// assert_eq!(store.load("scores").unwrap(), Some(json!({"alice": 3})));
+/// assert_eq!(store.load("reminders").unwrap(), None);
+/// # std::fs::remove_file(&path).unwrap();
+/// # }
+/// ```
+pub struct JsonFileStore {
+    path: PathBuf
+}
+
+impl JsonFileStore {
+    /// Keep state in a file, which is created the first time something is saved.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        JsonFileStore {
+            path: path.into()
+        }
+    }
+
+    fn read(&self) -> Result<Map<String, Value>, BotError> {
+        let mut contents = String::new();
+        match File::open(&self.path).and_then(|mut file| file.read_to_string(&mut contents)) {
+            Ok(_) => {},
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Map::new()),
+            Err(err) => return Err(storage_error(&self.path, err))
+        }
+
+        match serde_json::from_str(&contents[..]) {
+            Ok(Value::Object(values)) => Ok(values),
+            Ok(_) => Err(BotError::Storage(format!("{} doesn't have a JSON object in it", self.path.display()))),
+            Err(err) => Err(BotError::Storage(format!("{} isn't valid JSON: {}", self.path.display(), err)))
+        }
+    }
+}
+
+impl StateStore for JsonFileStore {
+    fn save(&self, key: &str, value: &Value) -> Result<(), BotError> {
+        let mut values = try!(self.read());
+        values.insert(key.to_owned(), value.clone());
+        let contents = try!(serde_json::to_string_pretty(&values));
+
+        // Writing somewhere else first means a crash halfway through can't lose what was there
+        let temp_path = self.path.with_extension("tmp");
+        let written = File::create(&temp_path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .and_then(|_| fs::rename(&temp_path, &self.path));
+        written.map_err(|err| storage_error(&self.path, err))
+    }
+
+    fn load(&self, key: &str) -> Result<Option<Value>, BotError> {
+        self.read().map(|mut values| values.remove(key))
+    }
+}
+
+fn storage_error(path: &PathBuf, err: io::Error) -> BotError {
+    BotError::Storage(format!("{}: {}", path.display(), err))
+}
+
+/// Where a bot saves its state, and how often.
+pub struct Persistence<T> {
+    store: Box<StateStore>,
+    key: String,
+    checkpoint: Option<Duration>,
+    last_saved: Instant,
+    to_json: fn(&T) -> Result<Value, BotError>,
+    from_json: fn(Value) -> Result<T, BotError>
+}
+
+impl<T> Persistence<T> {
+    pub fn new(store: Box<StateStore>, key: String, checkpoint: Option<Duration>) -> Self
+        where T: Serialize + DeserializeOwned {
+
+        Persistence {
+            store: store,
+            key: key,
+            checkpoint: checkpoint,
+            last_saved: Instant::now(),
+            to_json: to_json::<T>,
+            from_json: from_json::<T>
+        }
+    }
+
+    /// The state that was saved last, if any was.
+    pub fn load(&self) -> Result<Option<T>, BotError> {
+        match try!(self.store.load(&self.key[..])) {
+            Some(value) => (self.from_json)(value).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    pub fn save(&mut self, state: &T) -> Result<(), BotError> {
+        let value = try!((self.to_json)(state));
+        try!(self.store.save(&self.key[..], &value));
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+
+    /// Save the state if it's been long enough since it was last saved.
+    pub fn checkpoint(&mut self, state: &T) {
+        let due = self.checkpoint.map_or(false, |every| self.last_saved.elapsed() >= every);
+        if due {
+            debug!("Saving the bot's state");
+            if let Err(err) = self.save(state) {
+                warn!("Couldn't save the bot's state: {}", err);
+            }
+        }
+    }
+}
+
+fn to_json<T: Serialize>(state: &T) -> Result<Value, BotError> {
+    serde_json::to_value(state).map_err(BotError::from)
+}
+
+fn from_json<T: DeserializeOwned>(value: Value) -> Result<T, BotError> {
+    serde_json::from_value(value).map_err(|err| BotError::Storage(format!("Saved state doesn't fit: {}", err)))
+}
@@ -0,0 +1,135 @@
+/// Split a command into its arguments on whitespace, keeping quoted arguments together.
+///
+/// An argument that starts with a quote (`"`, `'`, or their curly versions) runs until the
+/// matching closing quote, so `remind "team standup" 9am` gives `remind`, `team standup` and
+/// `9am`. Quotes in the middle of an argument (like in `don't`) and quotes that are never closed
+/// are kept as they are.
+///
+/// Inside a quoted argument, a backslash escapes the closing quote or another backslash, so
+/// `"say \"hi\""` gives `say "hi"`. Backslashes anywhere else are kept as they are, so text
+/// without quotes splits exactly like it does on whitespace.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let mut token = String::new();
+        if let Some(close) = closing_quote(chars[i]) {
+            if let Some((quoted, end)) = read_quoted(&chars[..], i + 1, close) {
+                token.push_str(&quoted[..]);
+                i = end;
+            }
+        }
+
+        while i < chars.len() && !chars[i].is_whitespace() {
+            token.push(chars[i]);
+            i += 1;
+        }
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+fn closing_quote(c: char) -> Option<char> {
+    match c {
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        '\u{201C}' => Some('\u{201D}'),
+        '\u{2018}' => Some('\u{2019}'),
+        _ => None
+    }
+}
+
+/// The character escaped by a backslash at `i` in an argument quoted up to `close`, if there's a
+/// backslash there that escapes something.
+fn escaped(chars: &[char], i: usize, close: char) -> Option<char> {
+    if chars[i] != '\\' || i + 1 >= chars.len() {
+        return None;
+    }
+
+    let next = chars[i + 1];
+    if next == '\\' || next == close {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+/// Read up to the `close` quote, starting just after the opening quote. Returns the quoted text
+/// and the index just past the closing quote, or `None` if the quote is never closed.
+fn read_quoted(chars: &[char], start: usize, close: char) -> Option<(String, usize)> {
+    let mut quoted = String::new();
+    let mut i = start;
+    while i < chars.len() {
+        if chars[i] == close {
+            return Some((quoted, i + 1));
+        }
+
+        match escaped(chars, i, close) {
+            Some(c) => {
+                quoted.push(c);
+                i += 2;
+            },
+            None => {
+                quoted.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokenize;
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(tokenize("  deploy\tapp   prod\n"), vec!["deploy", "app", "prod"]);
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn keeps_quoted_arguments_together() {
+        assert_eq!(tokenize(r#"remind "team standup" 9am"#), vec!["remind", "team standup", "9am"]);
+        assert_eq!(tokenize("say 'hello there'"), vec!["say", "hello there"]);
+        assert_eq!(tokenize(r#"say """#), vec!["say", ""]);
+    }
+
+    #[test]
+    fn keeps_curly_quoted_arguments_together() {
+        assert_eq!(tokenize("remind \u{201C}team standup\u{201D} 9am"), vec!["remind", "team standup", "9am"]);
+        assert_eq!(tokenize("say \u{2018}hello there\u{2019}"), vec!["say", "hello there"]);
+    }
+
+    #[test]
+    fn keeps_quotes_that_are_never_closed() {
+        assert_eq!(tokenize(r#"say "hello there"#), vec!["say", "\"hello", "there"]);
+    }
+
+    #[test]
+    fn keeps_quotes_in_the_middle_of_words() {
+        assert_eq!(tokenize("I don't know"), vec!["I", "don't", "know"]);
+    }
+
+    #[test]
+    fn honours_escapes_inside_quotes() {
+        assert_eq!(tokenize(r#"say "a \"quoted\" word""#), vec!["say", "a \"quoted\" word"]);
+        assert_eq!(tokenize(r#"path "C:\\""#), vec!["path", "C:\\"]);
+        assert_eq!(tokenize(r#"grep "\d+""#), vec!["grep", "\\d+"]);
+    }
+
+    #[test]
+    fn keeps_backslashes_outside_quotes() {
+        assert_eq!(tokenize(r"a\ b"), vec!["a\\", "b"]);
+        assert_eq!(tokenize(r"grep \\d"), vec!["grep", "\\\\d"]);
+        assert_eq!(tokenize(r#"say \"hi\""#), vec!["say", "\\\"hi\\\""]);
+    }
+}
@@ -0,0 +1,176 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::lifecycle::{BotEvent, Subscribers};
+
+/// How long the watchdog's thread waits for something to watch before checking whether the
+/// watchdog is still around.
+const IDLE_CHECK_MILLIS: u64 = 1000;
+
+/// Keeps an eye on every command a bot is running, on one thread of its own, and does something
+/// about the ones that run for longer than they should.
+#[derive(Clone)]
+pub struct Watchdog {
+    shared: Arc<Shared>
+}
+
+struct Shared {
+    watched: Mutex<Watched>,
+
+    /// Notified when a command starts being watched.
+    changed: Condvar
+}
+
+struct Watched {
+    /// When each command overruns, soonest first. Commands that finished in time are left here
+    /// until then, and skipped.
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+
+    /// What to do about each command that's still running, if it overruns.
+    overruns: HashMap<u64, Box<FnOnce() + Send>>,
+    next_id: u64,
+    thread_started: bool
+}
+
+/// Keeps an eye on a command while its handler runs. The command is done when the watch is
+/// dropped.
+pub struct Watch {
+    watched: Option<(Arc<Shared>, u64)>
+}
+
+impl Watchdog {
+    pub fn new() -> Self {
+        let watched = Watched {
+            deadlines: BinaryHeap::new(),
+            overruns: HashMap::new(),
+            next_id: 0,
+            thread_started: false
+        };
+        Watchdog {
+            shared: Arc::new(Shared {
+                watched: Mutex::new(watched),
+                changed: Condvar::new()
+            })
+        }
+    }
+
+    /// Start watching a command, warning about it if it's still running after `timeout`.
+    pub fn watch(&self, timeout: Option<Duration>, command: &str, subscribers: &Subscribers) -> Watch {
+        let (command, subscribers) = (command.to_owned(), subscribers.clone());
+        self.watch_with(timeout, move || overran(command, timeout, &subscribers))
+    }
+
+    /// Start watching something, calling `on_overrun` from the watchdog's thread if it's still
+    /// running after `timeout`. With no timeout, there's nothing to watch for.
+    pub fn watch_with<F: FnOnce() + Send + 'static>(&self, timeout: Option<Duration>, on_overrun: F) -> Watch {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return Watch { watched: None }
+        };
+
+        let mut watched = self.shared.watched.lock().unwrap();
+        let id = watched.next_id;
+        watched.next_id += 1;
+        watched.deadlines.push(Reverse((Instant::now() + timeout, id)));
+        watched.overruns.insert(id, Box::new(on_overrun));
+        if !watched.thread_started {
+            watched.thread_started = true;
+            let shared = self.shared.clone();
+            thread::spawn(move || run_watchdog(shared));
+        }
+        self.shared.changed.notify_all();
+        Watch { watched: Some((self.shared.clone(), id)) }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        if let Some((ref shared, id)) = self.watched {
+            shared.watched.lock().unwrap().overruns.remove(&id);
+        }
+    }
+}
+
+/// Log a command that's overrun, and let anything watching the bot's events know.
+pub fn overran(command: String, timeout: Option<Duration>, subscribers: &Subscribers) {
+    let timeout = timeout.unwrap_or_default();
+    warn!("Command {} has been running for more than {:?}", command, timeout);
+    subscribers.send(BotEvent::CommandOverran {
+        command: command,
+        timeout: timeout
+    });
+}
+
+/// Deal with each command as it overruns, until the watchdog is dropped.
+fn run_watchdog(shared: Arc<Shared>) {
+    loop {
+        let on_overrun = {
+            let mut watched = shared.watched.lock().unwrap();
+            loop {
+                let next = watched.deadlines.peek().map(|&Reverse(deadline)| deadline);
+                match next {
+                    Some((deadline, id)) if deadline <= Instant::now() => {
+                        watched.deadlines.pop();
+                        if let Some(on_overrun) = watched.overruns.remove(&id) {
+                            break on_overrun;
+                        }
+                    },
+                    Some((deadline, _)) => {
+                        let wait = deadline - Instant::now();
+                        watched = shared.changed.wait_timeout(watched, wait).unwrap().0;
+                    },
+                    None => {
+                        // Only this thread is left holding the watchdog, so nothing else can be watched
+                        if Arc::strong_count(&shared) == 1 {
+                            return;
+                        }
+                        watched = shared.changed.wait_timeout(watched, Duration::from_millis(IDLE_CHECK_MILLIS)).unwrap().0;
+                    }
+                }
+            }
+        };
+
+        if panic::catch_unwind(AssertUnwindSafe(on_overrun)).is_err() {
+            warn!("Dealing with a command that overran panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::Watchdog;
+
+    #[test]
+    fn deals_with_commands_that_overrun() {
+        let watchdog = Watchdog::new();
+        let overran = Arc::new(Mutex::new(Vec::new()));
+        let watches = (0..3).map(|i| {
+            let overran = overran.clone();
+            watchdog.watch_with(Some(Duration::from_millis(20 * (3 - i))), move || overran.lock().unwrap().push(i))
+        }).collect::<Vec<_>>();
+
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*overran.lock().unwrap(), vec![2, 1, 0]);
+        drop(watches);
+    }
+
+    #[test]
+    fn leaves_commands_that_finish_in_time_alone() {
+        let watchdog = Watchdog::new();
+        let overran = Arc::new(Mutex::new(false));
+        let flag = overran.clone();
+        let watch = watchdog.watch_with(Some(Duration::from_millis(50)), move || *flag.lock().unwrap() = true);
+        drop(watch);
+
+        thread::sleep(Duration::from_millis(150));
+        assert!(!*overran.lock().unwrap());
+    }
+}
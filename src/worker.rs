@@ -0,0 +1,264 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicIsize;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use slack::RtmClient;
+
+use super::client::{ClientSnapshot, WORKER_MSG_ID_START};
+use super::lifecycle::{BotEvent, Subscribers};
+use super::metrics::{self, Metrics};
+use super::registry::{self, Commands, SendCommandHandler};
+use super::sender::CommandContext;
+use super::shutdown::Running;
+use super::watchdog::{self, Watchdog};
+
+/// A job for one of the worker threads.
+type Task = Box<FnOnce() + Send>;
+
+/// The threads a bot handles commands on when it isn't handling them on its event loop. See
+/// `SlackBot::worker_threads`.
+pub struct Workers<T> {
+    tasks: Sender<Task>,
+    threads: Arc<Threads>,
+    msg_ids: Arc<AtomicIsize>,
+
+    // Jobs can only go to other threads when the bot's state is `'static`, which is known where
+    // the workers are made but not where they're used
+    spawn: fn(&Workers<T>, CommandJob<T>)
+}
+
+/// What the worker threads share. A thread stuck on a command that overran is replaced with a
+/// new one, so there are `size` threads free to take commands, and once there are more than
+/// that, the next one to finish a command stops.
+struct Threads {
+    tasks: Mutex<Receiver<Task>>,
+    size: usize,
+    running: Mutex<usize>
+}
+
+impl<T> Workers<T> {
+    pub fn new(size: usize) -> Self where T: 'static {
+        let (tasks, queue) = mpsc::channel();
+        let threads = Arc::new(Threads {
+            tasks: Mutex::new(queue),
+            size: size,
+            running: Mutex::new(0)
+        });
+        for _ in 0..size {
+            add_thread(&threads);
+        }
+        Workers {
+            tasks: tasks,
+            threads: threads,
+            msg_ids: Arc::new(AtomicIsize::new(WORKER_MSG_ID_START)),
+            spawn: spawn_job::<T>
+        }
+    }
+
+    /// What the client knows right now, for commands handled on the workers. Messages sent from
+    /// any snapshot get their ids from the same counter, so they stay unique.
+    pub fn snapshot(&self, client: &RtmClient) -> Arc<ClientSnapshot> {
+        Arc::new(ClientSnapshot::new(client, self.msg_ids.clone()))
+    }
+
+    pub fn run(&self, job: CommandJob<T>) {
+        (self.spawn)(self, job);
+    }
+}
+
+/// Start another worker thread, which takes commands until the workers are dropped.
+fn add_thread(threads: &Arc<Threads>) {
+    *threads.running.lock().unwrap() += 1;
+    let threads = threads.clone();
+    thread::spawn(move || loop {
+        let task = match threads.tasks.lock().unwrap().recv() {
+            Ok(task) => task,
+            Err(_) => return
+        };
+        task();
+
+        let mut running = threads.running.lock().unwrap();
+        if *running > threads.size {
+            *running -= 1;
+            return;
+        }
+    });
+}
+
+/// A command to handle on a worker, with everything it needs from the bot.
+pub struct CommandJob<T> {
+    pub command_name: String,
+    pub handler: SendCommandHandler<T>,
+    pub ctx: CommandContext<'static>,
+    pub args: Vec<String>,
+    pub commands: Arc<Mutex<Commands<T>>>,
+    pub command_names: Arc<Mutex<Vec<String>>>,
+    pub metrics: Option<Arc<Metrics>>,
+    pub subscribers: Subscribers,
+    pub timeout: Option<Duration>,
+    pub watchdog: Watchdog,
+
+    /// Dropped once the job's done, so the bot doesn't wait for it any longer.
+    pub running: Running
+}
+
+impl<T> CommandJob<T> {
+    fn run(self, threads: Option<Arc<Threads>>) {
+        let CommandJob { command_name, mut handler, mut ctx, args, commands, command_names, metrics, subscribers, timeout, watchdog, running } = self;
+        let started = Instant::now();
+        // Whoever takes this reports the command as done: the job when it finishes, or the
+        // watchdog if it overruns first
+        let completion = Arc::new(Mutex::new(Some(running)));
+
+        let watch = {
+            let (command_name, subscribers, completion) = (command_name.clone(), subscribers.clone(), completion.clone());
+            watchdog.watch_with(timeout, move || {
+                watchdog::overran(command_name.clone(), timeout, &subscribers);
+                if let Some(running) = completion.lock().unwrap().take() {
+                    warn!("Abandoning command {}, which is put back if its handler ever finishes", command_name);
+                    // The thread it's stuck on is replaced, so the other commands still have as many
+                    if let Some(ref threads) = threads {
+                        add_thread(threads);
+                    }
+                    subscribers.send(BotEvent::CommandCompleted {
+                        command: command_name,
+                        duration: started.elapsed(),
+                        success: false
+                    });
+                    drop(running);
+                }
+            })
+        };
+        let result = {
+            let (handler, ctx, args) = (&mut handler, &mut ctx, &args);
+            // A panic would take the worker's thread with it and leave the command busy forever, so
+            // the handler is put back whatever happens
+            panic::catch_unwind(AssertUnwindSafe(|| metrics::time(metrics, &command_name[..], || {
+                if let Some(handler) = handler.without_state() {
+                    handler.handle(ctx, args);
+                }
+                !ctx.failed()
+            })))
+        };
+        drop(watch);
+
+        let success = match result {
+            Ok(success) => success,
+            Err(_) => {
+                error!("Command {} panicked on a worker thread", command_name);
+                false
+            }
+        };
+        let deregistered = ctx.deregistered();
+        registry::return_handler(&commands, &command_names, &command_name[..], handler, deregistered);
+        let running = completion.lock().unwrap().take();
+        if let Some(running) = running {
+            subscribers.send(BotEvent::CommandCompleted {
+                command: command_name,
+                duration: started.elapsed(),
+                success: success
+            });
+            drop(running);
+        }
+    }
+}
+
+fn spawn_job<T: 'static>(workers: &Workers<T>, job: CommandJob<T>) {
+    let threads = workers.threads.clone();
+    if workers.tasks.send(Box::new(move || job.run(Some(threads)))).is_err() {
+        error!("The worker threads have stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use super::{CommandJob, Workers};
+    use super::super::{CommandContext, Stateless};
+    use super::super::lifecycle::{BotEvent, Subscribers};
+    use super::super::registry::Commands;
+    use super::super::shutdown::InFlight;
+    use super::super::watchdog::Watchdog;
+
+    #[test]
+    fn handlers_that_panic_are_put_back_and_fail() {
+        let mut commands = Commands::new();
+        let handler = |_: &mut CommandContext, _: &Vec<String>| panic!("Couldn't deploy");
+        commands.insert("deploy".to_owned(), Box::new(Stateless(Box::new(handler))));
+        let handler = commands.take("deploy").unwrap();
+        let commands = Arc::new(Mutex::new(commands));
+        let subscribers = Subscribers::new();
+        let events = subscribers.subscribe();
+        let in_flight = InFlight::new();
+
+        CommandJob::<()> {
+            command_name: "deploy".to_owned(),
+            handler: handler,
+            ctx: CommandContext::test(),
+            args: vec![],
+            commands: commands.clone(),
+            command_names: Arc::new(Mutex::new(vec!["deploy".to_owned()])),
+            metrics: None,
+            subscribers: subscribers,
+            timeout: None,
+            watchdog: Watchdog::new(),
+            running: in_flight.start()
+        }.run(None);
+
+        assert!(commands.lock().unwrap().take("deploy").is_some());
+        match events.try_recv() {
+            Ok(BotEvent::CommandCompleted { command, success, .. }) => {
+                assert_eq!(command, "deploy");
+                assert!(!success);
+            },
+            other => panic!("Expected the command to complete, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn commands_that_overrun_are_abandoned_without_holding_up_the_rest() {
+        let mut commands = Commands::new();
+        let hang = |_: &mut CommandContext, _: &Vec<String>| thread::sleep(Duration::from_millis(500));
+        commands.insert("hang".to_owned(), Box::new(Stateless(Box::new(hang))));
+        commands.insert("status".to_owned(), Box::new(Stateless(Box::new(|_: &mut CommandContext, _: &Vec<String>| {}))));
+        let handlers = (commands.take("hang").unwrap(), commands.take("status").unwrap());
+        let commands = Arc::new(Mutex::new(commands));
+        let subscribers = Subscribers::new();
+        let events = subscribers.subscribe();
+        let (in_flight, watchdog) = (InFlight::new(), Watchdog::new());
+
+        let workers = Workers::<()>::new(1);
+        for (name, handler) in vec![("hang", handlers.0), ("status", handlers.1)] {
+            workers.run(CommandJob {
+                command_name: name.to_owned(),
+                handler: handler,
+                ctx: CommandContext::test(),
+                args: vec![],
+                commands: commands.clone(),
+                command_names: Arc::new(Mutex::new(vec![])),
+                metrics: None,
+                subscribers: subscribers.clone(),
+                timeout: Some(Duration::from_millis(20)),
+                watchdog: watchdog.clone(),
+                running: in_flight.start()
+            });
+        }
+
+        let completed = (0..2).map(|_| loop {
+            match events.recv_timeout(Duration::from_millis(250)) {
+                Ok(BotEvent::CommandCompleted { command, success, .. }) => break (command, success),
+                Ok(_) => continue,
+                Err(err) => panic!("Expected the commands to complete, got {:?}", err)
+            }
+        }).collect::<Vec<_>>();
+        assert_eq!(completed, vec![("hang".to_owned(), false), ("status".to_owned(), true)]);
+        assert!(commands.lock().unwrap().take("status").is_some());
+        in_flight.wait();
+    }
+}